@@ -5,88 +5,133 @@ use benchmark_rs::note_event_store::{
 #[cfg(test)]
 mod benches {
     use super::*;
-    use benchmark_rs::note_event_store::btree::BTreeNoteEventStore;
+    use benchmark_rs::note_event_store::{
+        btree::BTreeNoteEventStore, interval_tree::IntervalTreeNoteEventStore,
+        soa::SoaNoteEventStore,
+    };
     use rand::Rng;
     use test::Bencher;
 
-    fn bench_add_1000_random_events(b: &mut Bencher, store: &mut impl NoteEventStore) {
+    fn bench_add_1000_random_events<S: NoteEventStore>(b: &mut Bencher, new_store: fn() -> S) {
         let mut rng = rand::thread_rng();
 
         b.iter(|| {
+            let mut store = new_store();
             (0..1000).for_each(|i| {
                 let start_ticks = rng.gen_range(0..1000);
-                store.add_event(NoteEvent {
-                    id: i.to_string(),
-                    start_ticks,
-                    end_ticks: start_ticks + rng.gen_range(0..100),
-                    note_number: 60,
-                    velocity: 100,
-                })
-            })
+                store
+                    .add_event(NoteEvent {
+                        id: i.to_string(),
+                        start_ticks,
+                        end_ticks: start_ticks + rng.gen_range(0..100),
+                        note_number: 60,
+                        velocity: 100,
+                        channel: 0,
+                        track_id: 0,
+                    })
+                    .unwrap()
+            });
+            store
         });
     }
 
     #[bench]
     fn bench_add_1000_random_events_to_vec_store(b: &mut Bencher) {
-        let mut store = VecNoteEventStore::new();
-        bench_add_1000_random_events(b, &mut store);
+        bench_add_1000_random_events(b, VecNoteEventStore::new);
     }
 
     #[bench]
     fn bench_add_1000_random_events_to_btree_store(b: &mut Bencher) {
-        let mut store = BTreeNoteEventStore::new();
-        bench_add_1000_random_events(b, &mut store)
+        bench_add_1000_random_events(b, BTreeNoteEventStore::new);
     }
 
-    fn bench_add_1000_ascend_events(b: &mut Bencher, store: &mut impl NoteEventStore) {
+    #[bench]
+    fn bench_add_1000_random_events_to_interval_tree_store(b: &mut Bencher) {
+        bench_add_1000_random_events(b, IntervalTreeNoteEventStore::new);
+    }
+
+    #[bench]
+    fn bench_add_1000_random_events_to_soa_store(b: &mut Bencher) {
+        bench_add_1000_random_events(b, SoaNoteEventStore::new);
+    }
+
+    fn bench_add_1000_ascend_events<S: NoteEventStore>(b: &mut Bencher, new_store: fn() -> S) {
         b.iter(|| {
+            let mut store = new_store();
             (0..1000).for_each(|i| {
-                store.add_event(NoteEvent {
-                    id: i.to_string(),
-                    start_ticks: i,
-                    end_ticks: i + 10,
-                    note_number: 60,
-                    velocity: 100,
-                })
-            })
+                store
+                    .add_event(NoteEvent {
+                        id: i.to_string(),
+                        start_ticks: i,
+                        end_ticks: i + 10,
+                        note_number: 60,
+                        velocity: 100,
+                        channel: 0,
+                        track_id: 0,
+                    })
+                    .unwrap()
+            });
+            store
         });
     }
 
     #[bench]
     fn bench_add_1000_ascend_events_to_vec_store(b: &mut Bencher) {
-        let mut store = VecNoteEventStore::new();
-        bench_add_1000_ascend_events(b, &mut store);
+        bench_add_1000_ascend_events(b, VecNoteEventStore::new);
     }
 
     #[bench]
     fn bench_add_1000_ascend_events_to_btree_store(b: &mut Bencher) {
-        let mut store = BTreeNoteEventStore::new();
-        bench_add_1000_ascend_events(b, &mut store);
+        bench_add_1000_ascend_events(b, BTreeNoteEventStore::new);
+    }
+
+    #[bench]
+    fn bench_add_1000_ascend_events_to_interval_tree_store(b: &mut Bencher) {
+        bench_add_1000_ascend_events(b, IntervalTreeNoteEventStore::new);
     }
 
-    fn bench_add_1000_descend_events(b: &mut Bencher, store: &mut impl NoteEventStore) {
+    #[bench]
+    fn bench_add_1000_ascend_events_to_soa_store(b: &mut Bencher) {
+        bench_add_1000_ascend_events(b, SoaNoteEventStore::new);
+    }
+
+    fn bench_add_1000_descend_events<S: NoteEventStore>(b: &mut Bencher, new_store: fn() -> S) {
         b.iter(|| {
+            let mut store = new_store();
             (0..1000).rev().for_each(|i| {
-                store.add_event(NoteEvent {
-                    id: i.to_string(),
-                    start_ticks: i,
-                    end_ticks: i + 10,
-                    note_number: 60,
-                    velocity: 100,
-                })
-            })
+                store
+                    .add_event(NoteEvent {
+                        id: i.to_string(),
+                        start_ticks: i,
+                        end_ticks: i + 10,
+                        note_number: 60,
+                        velocity: 100,
+                        channel: 0,
+                        track_id: 0,
+                    })
+                    .unwrap()
+            });
+            store
         });
     }
 
     #[bench]
     fn bench_add_1000_descend_events_to_vec_store(b: &mut Bencher) {
-        let mut store = VecNoteEventStore::new();
-        bench_add_1000_descend_events(b, &mut store);
+        bench_add_1000_descend_events(b, VecNoteEventStore::new);
     }
 
     #[bench]
     fn bench_add_1000_descend_events_to_btree_store(b: &mut Bencher) {
-        let mut store = BTreeNoteEventStore::new();
-        bench_add_1000_descend_events(b, &mut store);
+        bench_add_1000_descend_events(b, BTreeNoteEventStore::new);
+    }
+
+    #[bench]
+    fn bench_add_1000_descend_events_to_interval_tree_store(b: &mut Bencher) {
+        bench_add_1000_descend_events(b, IntervalTreeNoteEventStore::new);
+    }
+
+    #[bench]
+    fn bench_add_1000_descend_events_to_soa_store(b: &mut Bencher) {
+        bench_add_1000_descend_events(b, SoaNoteEventStore::new);
     }
 }
@@ -0,0 +1,77 @@
+use benchmark_rs::note_event_store::{
+    base::NoteEventStore, event::NoteEvent, vec::VecNoteEventStore,
+};
+
+#[cfg(test)]
+mod benches {
+    use super::*;
+    use benchmark_rs::note_event_store::{
+        btree::BTreeNoteEventStore, interval_tree::IntervalTreeNoteEventStore,
+        pitch_interval::PitchIntervalNoteEventStore, soa::SoaNoteEventStore,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use test::Bencher;
+
+    fn add_10000_random_events(store: &mut impl NoteEventStore) {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        (0..10000).for_each(|i| {
+            let start_ticks = rng.gen_range(0..100_000);
+            store
+                .add_event(NoteEvent {
+                    id: i.to_string(),
+                    start_ticks,
+                    end_ticks: start_ticks + rng.gen_range(0..1000),
+                    note_number: rng.gen_range(0..128),
+                    velocity: 100,
+                    channel: 0,
+                    track_id: 0,
+                })
+                .unwrap()
+        });
+    }
+
+    fn bench_count_events_in_range_in_10000_events(
+        b: &mut Bencher,
+        store: &mut impl NoteEventStore,
+    ) {
+        add_10000_random_events(store);
+
+        b.iter(|| {
+            (0..100_000)
+                .step_by(2_000)
+                .map(|start_ticks| store.count_events_in_range(start_ticks, start_ticks + 1_999))
+                .sum::<usize>()
+        });
+    }
+
+    #[bench]
+    fn bench_count_events_in_range_in_10000_events_from_vec_store(b: &mut Bencher) {
+        let mut store = VecNoteEventStore::new();
+        bench_count_events_in_range_in_10000_events(b, &mut store);
+    }
+
+    #[bench]
+    fn bench_count_events_in_range_in_10000_events_from_btree_store(b: &mut Bencher) {
+        let mut store = BTreeNoteEventStore::new();
+        bench_count_events_in_range_in_10000_events(b, &mut store);
+    }
+
+    #[bench]
+    fn bench_count_events_in_range_in_10000_events_from_interval_tree_store(b: &mut Bencher) {
+        let mut store = IntervalTreeNoteEventStore::new();
+        bench_count_events_in_range_in_10000_events(b, &mut store);
+    }
+
+    #[bench]
+    fn bench_count_events_in_range_in_10000_events_from_pitch_interval_store(b: &mut Bencher) {
+        let mut store = PitchIntervalNoteEventStore::new();
+        bench_count_events_in_range_in_10000_events(b, &mut store);
+    }
+
+    #[bench]
+    fn bench_count_events_in_range_in_10000_events_from_soa_store(b: &mut Bencher) {
+        let mut store = SoaNoteEventStore::new();
+        bench_count_events_in_range_in_10000_events(b, &mut store);
+    }
+}
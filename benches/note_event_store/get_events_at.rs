@@ -0,0 +1,80 @@
+use benchmark_rs::note_event_store::{
+    base::NoteEventStore, event::NoteEvent, vec::VecNoteEventStore,
+};
+
+#[cfg(test)]
+mod benches {
+    use super::*;
+    use benchmark_rs::note_event_store::{
+        btree::BTreeNoteEventStore, interval_tree::IntervalTreeNoteEventStore,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use test::Bencher;
+
+    fn add_10000_random_events(store: &mut impl NoteEventStore) {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        (0..10000).for_each(|i| {
+            let start_ticks = rng.gen_range(0..100_000);
+            store
+                .add_event(NoteEvent {
+                    id: i.to_string(),
+                    start_ticks,
+                    end_ticks: start_ticks + rng.gen_range(0..1000),
+                    note_number: 60,
+                    velocity: 100,
+                    channel: 0,
+                    track_id: 0,
+                })
+                .unwrap()
+        });
+    }
+
+    fn bench_get_events_at_in_10000_events(b: &mut Bencher, store: &mut impl NoteEventStore) {
+        add_10000_random_events(store);
+
+        b.iter(|| {
+            (0..100_000)
+                .step_by(5_000)
+                .map(|ticks| store.iter_events_at(ticks).count())
+                .sum::<usize>()
+        });
+    }
+
+    #[bench]
+    fn bench_get_events_at_in_10000_events_from_vec_store(b: &mut Bencher) {
+        let mut store = VecNoteEventStore::new();
+        bench_get_events_at_in_10000_events(b, &mut store);
+    }
+
+    #[bench]
+    fn bench_get_events_at_in_10000_events_from_btree_store(b: &mut Bencher) {
+        let mut store = BTreeNoteEventStore::new();
+        bench_get_events_at_in_10000_events(b, &mut store);
+    }
+
+    // The default `iter_events_at`, answered by the btree's range query.
+    #[bench]
+    fn bench_get_events_at_by_range_in_10000_events_from_btree_store(b: &mut Bencher) {
+        let mut store = BTreeNoteEventStore::new();
+        add_10000_random_events(&mut store);
+
+        b.iter(|| {
+            (0..100_000)
+                .step_by(5_000)
+                .map(|ticks| {
+                    store
+                        .iter_events_by_range(ticks, ticks)
+                        .filter(|e| e.start_ticks <= ticks && ticks < e.end_ticks)
+                        .count()
+                })
+                .sum::<usize>()
+        });
+    }
+
+    #[bench]
+    fn bench_get_events_at_in_10000_events_from_interval_tree_store(b: &mut Bencher) {
+        let mut store = IntervalTreeNoteEventStore::new();
+        bench_get_events_at_in_10000_events(b, &mut store);
+    }
+}
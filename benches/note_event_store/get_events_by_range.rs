@@ -0,0 +1,69 @@
+use benchmark_rs::note_event_store::{
+    base::NoteEventStore, event::NoteEvent, vec::VecNoteEventStore,
+};
+
+#[cfg(test)]
+mod benches {
+    use super::*;
+    use benchmark_rs::note_event_store::{
+        btree::BTreeNoteEventStore, interval_tree::IntervalTreeNoteEventStore,
+        pitch_interval::PitchIntervalNoteEventStore, soa::SoaNoteEventStore,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use test::Bencher;
+
+    fn add_10000_random_events(store: &mut impl NoteEventStore) {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        (0..10000).for_each(|i| {
+            let start_ticks = rng.gen_range(0..1_000_000);
+            store
+                .add_event(NoteEvent {
+                    id: i.to_string(),
+                    start_ticks,
+                    end_ticks: start_ticks + rng.gen_range(0..1000),
+                    note_number: 60,
+                    velocity: 100,
+                    channel: 0,
+                    track_id: 0,
+                })
+                .unwrap()
+        });
+    }
+
+    fn bench_get_events_by_range_in_10000_events(b: &mut Bencher, store: &mut impl NoteEventStore) {
+        add_10000_random_events(store);
+
+        b.iter(|| store.get_events_by_range(500_000, 502_000));
+    }
+
+    #[bench]
+    fn bench_get_events_by_range_in_10000_events_from_vec_store(b: &mut Bencher) {
+        let mut store = VecNoteEventStore::new();
+        bench_get_events_by_range_in_10000_events(b, &mut store);
+    }
+
+    #[bench]
+    fn bench_get_events_by_range_in_10000_events_from_btree_store(b: &mut Bencher) {
+        let mut store = BTreeNoteEventStore::new();
+        bench_get_events_by_range_in_10000_events(b, &mut store);
+    }
+
+    #[bench]
+    fn bench_get_events_by_range_in_10000_events_from_interval_tree_store(b: &mut Bencher) {
+        let mut store = IntervalTreeNoteEventStore::new();
+        bench_get_events_by_range_in_10000_events(b, &mut store);
+    }
+
+    #[bench]
+    fn bench_get_events_by_range_in_10000_events_from_pitch_interval_store(b: &mut Bencher) {
+        let mut store = PitchIntervalNoteEventStore::new();
+        bench_get_events_by_range_in_10000_events(b, &mut store);
+    }
+
+    #[bench]
+    fn bench_get_events_by_range_in_10000_events_from_soa_store(b: &mut Bencher) {
+        let mut store = SoaNoteEventStore::new();
+        bench_get_events_by_range_in_10000_events(b, &mut store);
+    }
+}
@@ -0,0 +1,66 @@
+use benchmark_rs::{
+    note_event_store::{base::NoteEventStore, event::NoteEvent, vec::VecNoteEventStore},
+    smf::{
+        reader::import_smf,
+        writer::{export_smf, SmfExportOptions},
+    },
+};
+
+#[cfg(test)]
+mod benches {
+    use super::*;
+    use benchmark_rs::note_event_store::{
+        btree::BTreeNoteEventStore, interval_tree::IntervalTreeNoteEventStore,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use test::Bencher;
+
+    // A synthetic song of 5000 notes, written through the SMF exporter so the
+    // import path sees realistic delta times and running status.
+    fn song() -> Vec<u8> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut store = VecNoteEventStore::new();
+
+        (0..5000).for_each(|i| {
+            let start_ticks = i * 120 + rng.gen_range(0..120);
+            store
+                .add_event(NoteEvent {
+                    id: i.to_string(),
+                    start_ticks,
+                    end_ticks: start_ticks + rng.gen_range(30..960),
+                    note_number: 36 + (i % 48) as u8,
+                    velocity: rng.gen_range(1..128),
+                    channel: 0,
+                    track_id: 0,
+                })
+                .unwrap()
+        });
+
+        export_smf(&store, &SmfExportOptions::default()).unwrap()
+    }
+
+    fn bench_import_smf<S: NoteEventStore>(b: &mut Bencher, new_store: fn() -> S) {
+        let bytes = song();
+
+        b.iter(|| {
+            let mut store = new_store();
+            import_smf(&bytes, &mut store).unwrap();
+            store
+        });
+    }
+
+    #[bench]
+    fn bench_import_smf_to_vec_store(b: &mut Bencher) {
+        bench_import_smf(b, VecNoteEventStore::new);
+    }
+
+    #[bench]
+    fn bench_import_smf_to_btree_store(b: &mut Bencher) {
+        bench_import_smf(b, BTreeNoteEventStore::new);
+    }
+
+    #[bench]
+    fn bench_import_smf_to_interval_tree_store(b: &mut Bencher) {
+        bench_import_smf(b, IntervalTreeNoteEventStore::new);
+    }
+}
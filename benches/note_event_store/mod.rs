@@ -1 +1,7 @@
 mod add_event;
+mod count_events_in_range;
+mod get_events_at;
+mod get_events_by_pitch;
+mod get_events_by_range;
+mod get_events_in_region;
+mod import_smf;
@@ -4,7 +4,9 @@ extern crate test;
 
 use benchmark_rs::note_event_store::{
     base::NoteEventStore,
+    btree::BTreeNoteEventStore,
     event::{NoteEvent, NoteEventUpdate},
+    sorted_vec::SortedVecNoteEventStore,
     vec::VecNoteEventStore,
 };
 
@@ -15,9 +17,8 @@ mod benches {
 
     #[bench]
     fn bench_add_events(b: &mut Bencher) {
-        let mut store = VecNoteEventStore::new();
-
         b.iter(|| {
+            let mut store = VecNoteEventStore::new();
             store.add_events(vec![
                 NoteEvent {
                     id: "0".to_string(),
@@ -25,6 +26,8 @@ mod benches {
                     end_ticks: 10,
                     note_number: 60,
                     velocity: 100,
+                    channel: 0,
+                    track_id: 0,
                 },
                 NoteEvent {
                     id: "1".to_string(),
@@ -32,6 +35,8 @@ mod benches {
                     end_ticks: 20,
                     note_number: 70,
                     velocity: 90,
+                    channel: 0,
+                    track_id: 0,
                 },
             ])
         });
@@ -40,13 +45,17 @@ mod benches {
     #[bench]
     fn bench_update_event(b: &mut Bencher) {
         let mut store = VecNoteEventStore::new();
-        store.add_event(NoteEvent {
-            id: "0".to_string(),
-            start_ticks: 0,
-            end_ticks: 10,
-            note_number: 60,
-            velocity: 100,
-        });
+        store
+            .add_event(NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 10,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            })
+            .unwrap();
 
         b.iter(|| {
             store.update_event(NoteEventUpdate {
@@ -55,6 +64,8 @@ mod benches {
                 end_ticks: Some(15),
                 note_number: Some(70),
                 velocity: Some(90),
+                channel: None,
+                track_id: None,
             })
         });
     }
@@ -62,20 +73,28 @@ mod benches {
     #[bench]
     fn bench_update_events(b: &mut Bencher) {
         let mut store = VecNoteEventStore::new();
-        store.add_event(NoteEvent {
-            id: "0".to_string(),
-            start_ticks: 0,
-            end_ticks: 10,
-            note_number: 60,
-            velocity: 100,
-        });
-        store.add_event(NoteEvent {
-            id: "1".to_string(),
-            start_ticks: 10,
-            end_ticks: 20,
-            note_number: 70,
-            velocity: 90,
-        });
+        store
+            .add_event(NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 10,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            })
+            .unwrap();
+        store
+            .add_event(NoteEvent {
+                id: "1".to_string(),
+                start_ticks: 10,
+                end_ticks: 20,
+                note_number: 70,
+                velocity: 90,
+                channel: 0,
+                track_id: 0,
+            })
+            .unwrap();
 
         b.iter(|| {
             store.update_events(vec![
@@ -85,6 +104,8 @@ mod benches {
                     end_ticks: Some(15),
                     note_number: Some(70),
                     velocity: Some(90),
+                    channel: None,
+                    track_id: None,
                 },
                 NoteEventUpdate {
                     id: "1".to_string(),
@@ -92,6 +113,8 @@ mod benches {
                     end_ticks: Some(25),
                     note_number: Some(80),
                     velocity: Some(80),
+                    channel: None,
+                    track_id: None,
                 },
             ])
         });
@@ -100,13 +123,17 @@ mod benches {
     #[bench]
     fn bench_delete_event(b: &mut Bencher) {
         let mut store = VecNoteEventStore::new();
-        store.add_event(NoteEvent {
-            id: "0".to_string(),
-            start_ticks: 0,
-            end_ticks: 10,
-            note_number: 60,
-            velocity: 100,
-        });
+        store
+            .add_event(NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 10,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            })
+            .unwrap();
 
         b.iter(|| store.delete_event("0"));
     }
@@ -114,20 +141,28 @@ mod benches {
     #[bench]
     fn bench_delete_events(b: &mut Bencher) {
         let mut store = VecNoteEventStore::new();
-        store.add_event(NoteEvent {
-            id: "0".to_string(),
-            start_ticks: 0,
-            end_ticks: 10,
-            note_number: 60,
-            velocity: 100,
-        });
-        store.add_event(NoteEvent {
-            id: "1".to_string(),
-            start_ticks: 10,
-            end_ticks: 20,
-            note_number: 70,
-            velocity: 90,
-        });
+        store
+            .add_event(NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 10,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            })
+            .unwrap();
+        store
+            .add_event(NoteEvent {
+                id: "1".to_string(),
+                start_ticks: 10,
+                end_ticks: 20,
+                note_number: 70,
+                velocity: 90,
+                channel: 0,
+                track_id: 0,
+            })
+            .unwrap();
 
         b.iter(|| store.delete_events(vec!["0", "1"]));
     }
@@ -135,13 +170,17 @@ mod benches {
     #[bench]
     fn bench_get_event(b: &mut Bencher) {
         let mut store = VecNoteEventStore::new();
-        store.add_event(NoteEvent {
-            id: "0".to_string(),
-            start_ticks: 0,
-            end_ticks: 10,
-            note_number: 60,
-            velocity: 100,
-        });
+        store
+            .add_event(NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 10,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            })
+            .unwrap();
 
         b.iter(|| store.get_event("0"));
     }
@@ -150,38 +189,121 @@ mod benches {
     fn bench_get_events_by_range(b: &mut Bencher) {
         let mut store = VecNoteEventStore::new();
 
-        store.add_event(NoteEvent {
-            id: "0".to_string(),
-            start_ticks: 0,
-            end_ticks: 4,
-            note_number: 60,
-            velocity: 100,
-        });
+        store
+            .add_event(NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 4,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            })
+            .unwrap();
 
-        store.add_event(NoteEvent {
-            id: "1".to_string(),
-            start_ticks: 0,
-            end_ticks: 5,
-            note_number: 60,
-            velocity: 100,
-        });
+        store
+            .add_event(NoteEvent {
+                id: "1".to_string(),
+                start_ticks: 0,
+                end_ticks: 5,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            })
+            .unwrap();
+
+        store
+            .add_event(NoteEvent {
+                id: "2".to_string(),
+                start_ticks: 10,
+                end_ticks: 15,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            })
+            .unwrap();
+
+        store
+            .add_event(NoteEvent {
+                id: "3".to_string(),
+                start_ticks: 11,
+                end_ticks: 15,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            })
+            .unwrap();
+
+        b.iter(|| store.get_events_by_range(5, 10));
+    }
 
-        store.add_event(NoteEvent {
-            id: "2".to_string(),
-            start_ticks: 10,
-            end_ticks: 15,
-            note_number: 60,
-            velocity: 100,
+    // Recording appends events in roughly ascending order.
+    fn record_events(store: &mut impl NoteEventStore, count: u64) {
+        (0..count).for_each(|i| {
+            store
+                .add_event(NoteEvent {
+                    id: i.to_string(),
+                    start_ticks: i * 120,
+                    end_ticks: i * 120 + 60 + i % 7 * 60,
+                    note_number: 48 + (i % 24) as u8,
+                    velocity: 100,
+                    channel: 0,
+                    track_id: 0,
+                })
+                .unwrap()
         });
+    }
 
-        store.add_event(NoteEvent {
-            id: "3".to_string(),
-            start_ticks: 11,
-            end_ticks: 15,
-            note_number: 60,
-            velocity: 100,
+    fn bench_record_1000_events<S: NoteEventStore>(b: &mut Bencher, new_store: fn() -> S) {
+        b.iter(|| {
+            let mut store = new_store();
+            record_events(&mut store, 1000);
+            store
         });
+    }
 
-        b.iter(|| store.get_events_by_range(5, 10));
+    #[bench]
+    fn bench_record_1000_events_to_vec_store(b: &mut Bencher) {
+        bench_record_1000_events(b, VecNoteEventStore::new);
+    }
+
+    #[bench]
+    fn bench_record_1000_events_to_btree_store(b: &mut Bencher) {
+        bench_record_1000_events(b, BTreeNoteEventStore::new);
+    }
+
+    #[bench]
+    fn bench_record_1000_events_to_sorted_vec_store(b: &mut Bencher) {
+        bench_record_1000_events(b, SortedVecNoteEventStore::new);
+    }
+
+    fn bench_get_events_by_range_in_10000_recorded_events(
+        b: &mut Bencher,
+        store: &mut impl NoteEventStore,
+    ) {
+        record_events(store, 10000);
+
+        b.iter(|| store.get_events_by_range(600_000, 602_000));
+    }
+
+    #[bench]
+    fn bench_get_events_by_range_in_10000_recorded_events_from_vec_store(b: &mut Bencher) {
+        let mut store = VecNoteEventStore::new();
+        bench_get_events_by_range_in_10000_recorded_events(b, &mut store);
+    }
+
+    #[bench]
+    fn bench_get_events_by_range_in_10000_recorded_events_from_btree_store(b: &mut Bencher) {
+        let mut store = BTreeNoteEventStore::new();
+        bench_get_events_by_range_in_10000_recorded_events(b, &mut store);
+    }
+
+    #[bench]
+    fn bench_get_events_by_range_in_10000_recorded_events_from_sorted_vec_store(b: &mut Bencher) {
+        let mut store = SortedVecNoteEventStore::new();
+        bench_get_events_by_range_in_10000_recorded_events(b, &mut store);
     }
 }
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+
+use crate::note_event_store::{
+    base::NoteEventStore,
+    event::{NoteEvent, NoteEventOrder},
+};
+
+/// Two events on the same channel and note number where `second` starts
+/// while `first` is still sounding. A synth receives this as a retrigger,
+/// and the first note off cuts both notes short.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PitchOverlap<'a> {
+    pub first: &'a NoteEvent,
+    pub second: &'a NoteEvent,
+}
+
+/// The number of voices sounding from `ticks` until the next step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolyphonyStep {
+    pub ticks: u64,
+    pub voices: usize,
+}
+
+/// Finds every pair of overlapping events on the same channel and note
+/// number, ordered by `second` and then `first` in
+/// `NoteEventOrder::StartTicks` order. Zero-length events only overlap the
+/// events sounding at their start, and never anything starting after them.
+pub fn find_pitch_overlaps(store: &impl NoteEventStore) -> Vec<PitchOverlap<'_>> {
+    let mut lanes: HashMap<(u8, u8), Vec<&NoteEvent>> = HashMap::new();
+    for event in store.iter_events() {
+        lanes
+            .entry((event.channel, event.note_number))
+            .or_default()
+            .push(event);
+    }
+
+    let mut overlaps = vec![];
+    for mut events in lanes.into_values() {
+        events.sort_unstable_by(|a, b| NoteEventOrder::StartTicks.compare(a, b));
+
+        let mut sounding: Vec<&NoteEvent> = vec![];
+        for event in events {
+            sounding.retain(|first| first.end_ticks > event.start_ticks);
+            overlaps.extend(sounding.iter().map(|first| PitchOverlap {
+                first,
+                second: event,
+            }));
+            if event.start_ticks < event.end_ticks {
+                sounding.push(event);
+            }
+        }
+    }
+
+    overlaps.sort_unstable_by(|a, b| {
+        NoteEventOrder::StartTicks
+            .compare(a.second, b.second)
+            .then_with(|| NoteEventOrder::StartTicks.compare(a.first, b.first))
+    });
+    overlaps
+}
+
+/// The number of events sounding, in the sense of
+/// `NoteEventStore::iter_events_at`, at every tick in
+/// `start_ticks..=end_ticks`. The first step is at `start_ticks` and a new
+/// step only starts where the number changes. Empty for an inverted range.
+pub fn polyphony_curve_in_range(
+    store: &impl NoteEventStore,
+    start_ticks: u64,
+    end_ticks: u64,
+) -> Vec<PolyphonyStep> {
+    if start_ticks > end_ticks {
+        return vec![];
+    }
+
+    // Each change is the tick it applies from and whether a voice starts or
+    // stops there. Stops past the range never show up in the curve.
+    let mut changes: Vec<(u64, bool)> = vec![];
+    for event in store.iter_events_by_range(start_ticks, end_ticks) {
+        if event.start_ticks == event.end_ticks || event.end_ticks <= start_ticks {
+            continue;
+        }
+        changes.push((event.start_ticks.max(start_ticks), true));
+        if event.end_ticks <= end_ticks {
+            changes.push((event.end_ticks, false));
+        }
+    }
+    changes.sort_unstable();
+
+    let mut curve = vec![PolyphonyStep {
+        ticks: start_ticks,
+        voices: 0,
+    }];
+    let mut voices = 0;
+    let mut changes = changes.into_iter().peekable();
+    while let Some((ticks, is_start)) = changes.next() {
+        voices = if is_start { voices + 1 } else { voices - 1 };
+        if changes
+            .peek()
+            .is_some_and(|(next_ticks, _)| *next_ticks == ticks)
+        {
+            continue;
+        }
+
+        let last = curve.last_mut().unwrap();
+        if last.ticks == ticks {
+            last.voices = voices;
+        } else if last.voices != voices {
+            curve.push(PolyphonyStep { ticks, voices });
+        }
+    }
+    curve
+}
+
+/// The first step of `polyphony_curve_in_range` with the most voices.
+pub fn peak_polyphony_in_range(
+    store: &impl NoteEventStore,
+    start_ticks: u64,
+    end_ticks: u64,
+) -> Option<PolyphonyStep> {
+    polyphony_curve_in_range(store, start_ticks, end_ticks)
+        .into_iter()
+        .reduce(|peak, step| {
+            if step.voices > peak.voices {
+                step
+            } else {
+                peak
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::note_event_store::{btree::BTreeNoteEventStore, vec::VecNoteEventStore};
+
+    use super::*;
+
+    fn note(id: &str, start_ticks: u64, end_ticks: u64, note_number: u8) -> NoteEvent {
+        NoteEvent {
+            id: id.to_string(),
+            start_ticks,
+            end_ticks,
+            note_number,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        }
+    }
+
+    fn step(ticks: u64, voices: usize) -> PolyphonyStep {
+        PolyphonyStep { ticks, voices }
+    }
+
+    fn add_random_events(store: &mut impl NoteEventStore, rng: &mut StdRng) {
+        for i in 0..500 {
+            let start_ticks = rng.gen_range(0..10000);
+            store
+                .add_event(NoteEvent {
+                    channel: rng.gen_range(0..2),
+                    ..note(
+                        &i.to_string(),
+                        start_ticks,
+                        start_ticks + rng.gen_range(0..500),
+                        rng.gen_range(60..72),
+                    )
+                })
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_find_pitch_overlaps() {
+        let mut store = VecNoteEventStore::new();
+        store
+            .add_events(vec![
+                note("0", 0, 100, 60),
+                note("1", 50, 150, 60),
+                note("2", 60, 70, 60),
+                // Back to back with "1".
+                note("3", 150, 200, 60),
+                note("4", 0, 200, 62),
+                // Zero-length events only overlap what is sounding already.
+                note("5", 180, 180, 60),
+                note("6", 180, 190, 60),
+                // Sent on another channel, so it never cuts "0" short.
+                NoteEvent {
+                    channel: 1,
+                    ..note("7", 0, 100, 60)
+                },
+            ])
+            .unwrap();
+
+        let ids: Vec<(&str, &str)> = find_pitch_overlaps(&store)
+            .iter()
+            .map(|overlap| (overlap.first.id.as_str(), overlap.second.id.as_str()))
+            .collect();
+
+        assert_eq!(
+            ids,
+            vec![("0", "1"), ("0", "2"), ("1", "2"), ("3", "5"), ("3", "6")]
+        );
+    }
+
+    #[test]
+    fn test_find_pitch_overlaps_random() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut store = BTreeNoteEventStore::new();
+        add_random_events(&mut store, &mut rng);
+
+        let overlaps = find_pitch_overlaps(&store);
+        let expected = store
+            .iter_events()
+            .flat_map(|first| store.iter_events().map(move |second| (first, second)))
+            .filter(|(first, second)| {
+                first.channel == second.channel
+                    && first.note_number == second.note_number
+                    && NoteEventOrder::StartTicks.compare(first, second).is_lt()
+                    && first.start_ticks < first.end_ticks
+                    && second.start_ticks < first.end_ticks
+            })
+            .count();
+
+        assert!(!overlaps.is_empty());
+        assert_eq!(overlaps.len(), expected);
+        for overlap in overlaps {
+            assert_eq!(overlap.first.note_number, overlap.second.note_number);
+            assert!(overlap.second.start_ticks < overlap.first.end_ticks);
+        }
+    }
+
+    #[test]
+    fn test_polyphony_curve_in_range() {
+        let mut store = VecNoteEventStore::new();
+        store
+            .add_events(vec![
+                note("0", 0, 100, 60),
+                note("1", 50, 100, 64),
+                note("2", 100, 150, 67),
+                note("3", 120, 120, 72),
+                note("4", 200, 300, 60),
+            ])
+            .unwrap();
+
+        assert_eq!(
+            polyphony_curve_in_range(&store, 0, 400),
+            vec![
+                step(0, 1),
+                step(50, 2),
+                step(100, 1),
+                step(150, 0),
+                step(200, 1),
+                step(300, 0),
+            ]
+        );
+        assert_eq!(
+            polyphony_curve_in_range(&store, 60, 250),
+            vec![step(60, 2), step(100, 1), step(150, 0), step(200, 1)]
+        );
+        assert_eq!(
+            polyphony_curve_in_range(&store, 160, 190),
+            vec![step(160, 0)]
+        );
+        assert!(polyphony_curve_in_range(&store, 100, 50).is_empty());
+
+        assert_eq!(peak_polyphony_in_range(&store, 0, 400), Some(step(50, 2)));
+        assert_eq!(
+            peak_polyphony_in_range(&store, 120, 400),
+            Some(step(120, 1))
+        );
+        assert_eq!(peak_polyphony_in_range(&store, 100, 50), None);
+    }
+
+    #[test]
+    fn test_polyphony_curve_in_range_random() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut store = BTreeNoteEventStore::new();
+        add_random_events(&mut store, &mut rng);
+
+        for _ in 0..20 {
+            let start_ticks = rng.gen_range(0..10500);
+            let end_ticks = start_ticks + rng.gen_range(0..1000);
+            let curve = polyphony_curve_in_range(&store, start_ticks, end_ticks);
+
+            assert_eq!(curve[0].ticks, start_ticks);
+            for steps in curve.windows(2) {
+                assert!(steps[0].ticks < steps[1].ticks);
+                assert_ne!(steps[0].voices, steps[1].voices);
+            }
+            for _ in 0..20 {
+                let ticks = rng.gen_range(start_ticks..=end_ticks);
+                let step = curve.iter().rev().find(|step| step.ticks <= ticks).unwrap();
+                assert_eq!(step.voices, store.iter_events_at(ticks).count());
+            }
+            assert_eq!(
+                peak_polyphony_in_range(&store, start_ticks, end_ticks)
+                    .unwrap()
+                    .voices,
+                store.max_polyphony_in_range(start_ticks, end_ticks)
+            );
+        }
+    }
+}
@@ -1 +1,4 @@
+pub mod analysis;
 pub mod note_event_store;
+pub mod smf;
+pub mod timing;
@@ -1,12 +1,404 @@
-use super::event::{NoteEvent, NoteEventUpdate};
+use std::ops::RangeInclusive;
+
+use crate::timing::{meter_map::MeterMap, tempo_map::TempoMap};
+
+use super::{
+    error::NoteEventStoreError,
+    event::{NoteEvent, NoteEventOrder, NoteEventUpdate, RangeSemantics, VelocityStats},
+    query::NoteEventQuery,
+    transaction::NoteEventTransaction,
+};
 
 pub trait NoteEventStore {
-    fn add_event(&mut self, event: NoteEvent);
-    fn add_events(&mut self, events: Vec<NoteEvent>);
-    fn update_event(&mut self, event: NoteEventUpdate);
-    fn update_events(&mut self, events: Vec<NoteEventUpdate>);
-    fn delete_event(&mut self, id: &str);
-    fn delete_events(&mut self, ids: Vec<&str>);
+    fn add_event(&mut self, event: NoteEvent) -> Result<(), NoteEventStoreError>;
+    fn update_event(&mut self, event: NoteEventUpdate) -> Result<(), NoteEventStoreError>;
+    fn delete_event(&mut self, id: &str) -> Result<NoteEvent, NoteEventStoreError>;
     fn get_event(&self, id: &str) -> Option<&NoteEvent>;
-    fn get_events_by_range(&self, start_ticks: u64, end_ticks: u64) -> Vec<&NoteEvent>;
+
+    /// Runs `f` and keeps its changes only if it returns `Ok`; otherwise every
+    /// change made through the transaction is reverted. If a revert fails, the
+    /// error is `NoteEventStoreError::RollbackFailed` and the changes not yet
+    /// reverted stay applied.
+    fn transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut NoteEventTransaction<'_, Self>) -> Result<T, NoteEventStoreError>,
+    ) -> Result<T, NoteEventStoreError>
+    where
+        Self: Sized,
+    {
+        self.begin_transaction();
+        let mut transaction = NoteEventTransaction::new(self);
+        let (result, committed) = match f(&mut transaction) {
+            Ok(value) => (Ok(value), true),
+            Err(error) => match transaction.rollback() {
+                Ok(()) => (Err(error), false),
+                // Whatever could not be reverted stays applied, so it ends up
+                // kept like a commit.
+                Err(rollback_error) => (
+                    Err(NoteEventStoreError::rollback_failed(error, rollback_error)),
+                    true,
+                ),
+            },
+        };
+        self.end_transaction(committed);
+        result
+    }
+
+    /// Marks the start of a transaction run on this store or on a store
+    /// wrapping it, so wrappers can treat the changes until the matching
+    /// `end_transaction` as one unit. Wrappers forward both calls to the
+    /// store they wrap.
+    fn begin_transaction(&mut self) {}
+
+    /// Ends the innermost transaction. When `committed` is `false`, its
+    /// changes have already been reverted, and the reverts were made inside
+    /// the transaction too.
+    fn end_transaction(&mut self, _committed: bool) {}
+
+    /// Adds all events, or none of them if any fails.
+    fn add_events(&mut self, events: Vec<NoteEvent>) -> Result<(), NoteEventStoreError>
+    where
+        Self: Sized,
+    {
+        self.transaction(|transaction| {
+            for event in events {
+                transaction.add_event(event)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Applies all updates, or none of them if any fails.
+    fn update_events(&mut self, events: Vec<NoteEventUpdate>) -> Result<(), NoteEventStoreError>
+    where
+        Self: Sized,
+    {
+        self.transaction(|transaction| {
+            for event in events {
+                transaction.update_event(event)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Deletes all events, or none of them if any fails.
+    fn delete_events(&mut self, ids: Vec<&str>) -> Result<Vec<NoteEvent>, NoteEventStoreError>
+    where
+        Self: Sized,
+    {
+        self.transaction(|transaction| {
+            ids.into_iter()
+                .map(|id| transaction.delete_event(id))
+                .collect()
+        })
+    }
+
+    /// Deletes every event on `track_id`, or none of them if any fails.
+    fn delete_events_on_track(
+        &mut self,
+        track_id: u16,
+    ) -> Result<Vec<NoteEvent>, NoteEventStoreError>
+    where
+        Self: Sized,
+    {
+        let mut events: Vec<&NoteEvent> = self.iter_events_on_track(track_id).collect();
+        events.sort_unstable_by(|a, b| NoteEventOrder::StartTicks.compare(a, b));
+        let ids: Vec<String> = events.into_iter().map(|e| e.id.clone()).collect();
+        self.delete_events(ids.iter().map(String::as_str).collect())
+    }
+
+    /// Yields every event in no particular order.
+    fn iter_events(&self) -> impl Iterator<Item = &NoteEvent>;
+
+    /// Yields events matching `RangeSemantics::Closed` in no particular order;
+    /// use `get_events_by_range` when the order matters.
+    fn iter_events_by_range(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &NoteEvent>;
+
+    /// Yields every event on `track_id` in no particular order.
+    fn iter_events_on_track(&self, track_id: u16) -> impl Iterator<Item = &NoteEvent> {
+        self.iter_events().filter(move |e| e.track_id == track_id)
+    }
+
+    fn iter_events_by_range_on_track(
+        &self,
+        track_id: u16,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.iter_events_by_range(start_ticks, end_ticks)
+            .filter(move |e| e.track_id == track_id)
+    }
+
+    fn get_events_by_range_on_track(
+        &self,
+        track_id: u16,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> Vec<&NoteEvent> {
+        let mut events: Vec<&NoteEvent> = self
+            .iter_events_by_range_on_track(track_id, start_ticks, end_ticks)
+            .collect();
+        events.sort_unstable_by(|a, b| NoteEventOrder::StartTicks.compare(a, b));
+        events
+    }
+
+    fn get_events_by_range(&self, start_ticks: u64, end_ticks: u64) -> Vec<&NoteEvent> {
+        self.get_events_by_range_ordered(start_ticks, end_ticks, NoteEventOrder::StartTicks)
+    }
+
+    fn get_events_by_range_ordered(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+        order: NoteEventOrder,
+    ) -> Vec<&NoteEvent> {
+        let mut events: Vec<&NoteEvent> =
+            self.iter_events_by_range(start_ticks, end_ticks).collect();
+        events.sort_unstable_by(|a, b| order.compare(a, b));
+        events
+    }
+
+    /// Like `iter_events_by_range`, matching events with the given semantics
+    /// rather than always treating both ends as inclusive.
+    fn iter_events_by_range_with(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+        semantics: RangeSemantics,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        // Every other semantics matches a subset of the closed range.
+        self.iter_events_by_range(start_ticks, end_ticks)
+            .filter(move |e| semantics.overlaps(e, start_ticks, end_ticks))
+    }
+
+    fn get_events_by_range_with(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+        semantics: RangeSemantics,
+    ) -> Vec<&NoteEvent> {
+        let mut events: Vec<&NoteEvent> = self
+            .iter_events_by_range_with(start_ticks, end_ticks, semantics)
+            .collect();
+        events.sort_unstable_by(|a, b| NoteEventOrder::StartTicks.compare(a, b));
+        events
+    }
+
+    /// Yields events sounding at `ticks`, i.e. those with
+    /// `start_ticks <= ticks < end_ticks`, in no particular order. Unlike a
+    /// range query, events ending exactly at `ticks` are excluded.
+    fn iter_events_at(&self, ticks: u64) -> impl Iterator<Item = &NoteEvent> {
+        self.iter_events_by_range(ticks, ticks)
+            .filter(move |e| e.start_ticks <= ticks && ticks < e.end_ticks)
+    }
+
+    fn get_events_at(&self, ticks: u64) -> Vec<&NoteEvent> {
+        let mut events: Vec<&NoteEvent> = self.iter_events_at(ticks).collect();
+        events.sort_unstable_by(|a, b| NoteEventOrder::StartTicks.compare(a, b));
+        events
+    }
+
+    /// The number of events `iter_events_by_range` would yield. This walks
+    /// the matches, so it is linear in them; only a store keeping order
+    /// statistics could do better.
+    fn count_events_in_range(&self, start_ticks: u64, end_ticks: u64) -> usize {
+        self.iter_events_by_range(start_ticks, end_ticks).count()
+    }
+
+    /// The most events sounding at once, in the sense of `iter_events_at`, at
+    /// any tick in `start_ticks..=end_ticks`.
+    fn max_polyphony_in_range(&self, start_ticks: u64, end_ticks: u64) -> usize {
+        max_polyphony(
+            self.iter_events_by_range(start_ticks, end_ticks)
+                .map(|e| (e.start_ticks, e.end_ticks)),
+            start_ticks,
+            end_ticks,
+        )
+    }
+
+    /// The lowest and highest note numbers of the events in the range.
+    fn pitch_bounds_in_range(&self, start_ticks: u64, end_ticks: u64) -> Option<(u8, u8)> {
+        self.iter_events_by_range(start_ticks, end_ticks)
+            .map(|e| (e.note_number, e.note_number))
+            .reduce(|(low, high), (note, _)| (low.min(note), high.max(note)))
+    }
+
+    fn velocity_stats_in_range(&self, start_ticks: u64, end_ticks: u64) -> Option<VelocityStats> {
+        VelocityStats::from_velocities(
+            self.iter_events_by_range(start_ticks, end_ticks)
+                .map(|e| e.velocity),
+        )
+    }
+
+    /// The first event starting after `ticks`, in `NoteEventOrder::StartTicks`
+    /// order.
+    fn next_event_after(&self, ticks: u64) -> Option<&NoteEvent> {
+        self.iter_events()
+            .filter(|e| e.start_ticks > ticks)
+            .min_by(|a, b| NoteEventOrder::StartTicks.compare(a, b))
+    }
+
+    /// The last event starting before `ticks`, in `NoteEventOrder::StartTicks`
+    /// order.
+    fn prev_event_before(&self, ticks: u64) -> Option<&NoteEvent> {
+        self.iter_events()
+            .filter(|e| e.start_ticks < ticks)
+            .max_by(|a, b| NoteEventOrder::StartTicks.compare(a, b))
+    }
+
+    /// The first event with `note_number` starting after `ticks`.
+    fn next_event_with_pitch(&self, ticks: u64, note_number: u8) -> Option<&NoteEvent> {
+        self.iter_events()
+            .filter(|e| e.start_ticks > ticks && e.note_number == note_number)
+            .min_by(|a, b| NoteEventOrder::StartTicks.compare(a, b))
+    }
+
+    /// Yields events with `note_number` in no particular order.
+    fn iter_events_by_pitch(&self, note_number: u8) -> impl Iterator<Item = &NoteEvent> {
+        self.iter_events_by_pitch_range(note_number, note_number)
+    }
+
+    fn get_events_by_pitch(&self, note_number: u8) -> Vec<&NoteEvent> {
+        self.get_events_by_pitch_range(note_number, note_number)
+    }
+
+    /// Yields events with a note number in `low_note..=high_note` in no
+    /// particular order.
+    fn iter_events_by_pitch_range(
+        &self,
+        low_note: u8,
+        high_note: u8,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.iter_events()
+            .filter(move |e| (low_note..=high_note).contains(&e.note_number))
+    }
+
+    fn get_events_by_pitch_range(&self, low_note: u8, high_note: u8) -> Vec<&NoteEvent> {
+        let mut events: Vec<&NoteEvent> = self
+            .iter_events_by_pitch_range(low_note, high_note)
+            .collect();
+        events.sort_unstable_by(|a, b| NoteEventOrder::StartTicks.compare(a, b));
+        events
+    }
+
+    /// Yields events overlapping `start_ticks..=end_ticks` whose note number
+    /// lies in `low_note..=high_note`, in no particular order.
+    fn iter_events_in_region(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+        low_note: u8,
+        high_note: u8,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.iter_events_by_range(start_ticks, end_ticks)
+            .filter(move |e| (low_note..=high_note).contains(&e.note_number))
+    }
+
+    fn get_events_in_region(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+        low_note: u8,
+        high_note: u8,
+    ) -> Vec<&NoteEvent> {
+        let mut events: Vec<&NoteEvent> = self
+            .iter_events_in_region(start_ticks, end_ticks, low_note, high_note)
+            .collect();
+        events.sort_unstable_by(|a, b| NoteEventOrder::StartTicks.compare(a, b));
+        events
+    }
+
+    /// Yields events sounding at any point between `start_secs` and
+    /// `end_secs`, in no particular order.
+    fn iter_events_by_time_range(
+        &self,
+        tempo_map: &TempoMap,
+        start_secs: f64,
+        end_secs: f64,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        let (start_ticks, end_ticks) = tempo_map.seconds_range_to_ticks(start_secs, end_secs);
+        self.iter_events_by_range(start_ticks, end_ticks)
+    }
+
+    fn get_events_by_time_range(
+        &self,
+        tempo_map: &TempoMap,
+        start_secs: f64,
+        end_secs: f64,
+    ) -> Vec<&NoteEvent> {
+        let mut events: Vec<&NoteEvent> = self
+            .iter_events_by_time_range(tempo_map, start_secs, end_secs)
+            .collect();
+        events.sort_unstable_by(|a, b| NoteEventOrder::StartTicks.compare(a, b));
+        events
+    }
+
+    fn iter_events_in_bars(
+        &self,
+        meter_map: &MeterMap,
+        bars: RangeInclusive<u32>,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        meter_map
+            .bars_to_ticks(bars)
+            .into_iter()
+            .flat_map(move |(start_ticks, end_ticks)| {
+                self.iter_events_by_range(start_ticks, end_ticks)
+            })
+    }
+
+    fn get_events_in_bars(
+        &self,
+        meter_map: &MeterMap,
+        bars: RangeInclusive<u32>,
+    ) -> Vec<&NoteEvent> {
+        let mut events: Vec<&NoteEvent> = self.iter_events_in_bars(meter_map, bars).collect();
+        events.sort_unstable_by(|a, b| NoteEventOrder::StartTicks.compare(a, b));
+        events
+    }
+
+    /// Starts a query whose constraints are combined and planned against
+    /// the store's own range, pitch and track queries.
+    fn query(&self) -> NoteEventQuery<'_, Self>
+    where
+        Self: Sized,
+    {
+        NoteEventQuery::new(self)
+    }
+}
+
+/// Sweeps `(start_ticks, end_ticks)` pairs for the most of them sounding at
+/// once within `start_ticks..=end_ticks`.
+pub(crate) fn max_polyphony(
+    ticks: impl Iterator<Item = (u64, u64)>,
+    start_ticks: u64,
+    end_ticks: u64,
+) -> usize {
+    if start_ticks > end_ticks {
+        return 0;
+    }
+
+    // Ends sort before starts at the same tick, so back-to-back events never
+    // count as sounding together. Every start is clamped into the range, and
+    // the maximum is reached at a start, so ends past the range don't matter.
+    let mut changes: Vec<(u64, bool)> = ticks
+        .filter(|&(start, end)| start < end && start <= end_ticks && end > start_ticks)
+        .flat_map(|(start, end)| [(start.max(start_ticks), true), (end, false)])
+        .collect();
+    changes.sort_unstable();
+
+    let mut sounding = 0;
+    let mut max_sounding = 0;
+    for (_, is_start) in changes {
+        if is_start {
+            sounding += 1;
+            max_sounding = max_sounding.max(sounding);
+        } else {
+            sounding -= 1;
+        }
+    }
+    max_sounding
 }
@@ -1,175 +1,423 @@
 use std::{
-    cell::RefCell,
+    cmp::Ordering,
     collections::{BTreeMap, HashMap, HashSet},
+    mem,
+    ops::Bound,
 };
 
 use super::{
     base::NoteEventStore,
-    event::{NoteEvent, NoteEventUpdate},
+    error::NoteEventStoreError,
+    event::{NoteEvent, NoteEventOrder, NoteEventUpdate, RangeSemantics, TimedEvent},
+    id::{NoteEventId, NoteEventIdInterner},
 };
 
-pub struct BTreeNoteEventStore {
-    events: HashMap<String, NoteEvent>,
-    start_ticks_index: BTreeMap<u64, HashSet<String>>,
-    end_ticks_index: BTreeMap<u64, HashSet<String>>,
+/// Indexes any kind of `TimedEvent` by its start and end ticks.
+pub struct BTreeEventStore<E> {
+    // String ids are interned once so the indices only hold copyable ids.
+    ids: NoteEventIdInterner,
+    events: HashMap<NoteEventId, E>,
+    start_ticks_index: BTreeMap<u64, HashSet<NoteEventId>>,
+    end_ticks_index: BTreeMap<u64, HashSet<NoteEventId>>,
+    track_index: BTreeMap<u16, HashSet<NoteEventId>>,
+    pitch_index: BTreeMap<u8, HashSet<NoteEventId>>,
+    // How many events last each `end_ticks - start_ticks`. The longest bounds
+    // how far before a tick an event sounding at it can start.
+    durations: BTreeMap<u64, usize>,
 }
 
-impl BTreeNoteEventStore {
-    pub fn new() -> Self {
-        BTreeNoteEventStore {
+pub type BTreeNoteEventStore = BTreeEventStore<NoteEvent>;
+
+impl<E> Default for BTreeEventStore<E> {
+    fn default() -> Self {
+        BTreeEventStore {
+            ids: NoteEventIdInterner::new(),
             events: HashMap::new(),
             start_ticks_index: BTreeMap::new(),
             end_ticks_index: BTreeMap::new(),
+            track_index: BTreeMap::new(),
+            pitch_index: BTreeMap::new(),
+            durations: BTreeMap::new(),
         }
     }
 }
 
-impl NoteEventStore for BTreeNoteEventStore {
-    fn add_event(&mut self, event: NoteEvent) {
-        let id = event.id.clone();
-        self.events.insert(id.clone(), event.clone());
+impl<E: TimedEvent> BTreeEventStore<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn note_event_id(&self, id: &str) -> Option<NoteEventId> {
+        self.ids.get(id)
+    }
 
-        let start_ticks = event.start_ticks;
-        let end_ticks = event.end_ticks;
+    pub fn get_event_by_note_event_id(&self, id: NoteEventId) -> Option<&E> {
+        self.events.get(&id)
+    }
+
+    pub fn add_event(&mut self, event: E) -> Result<(), NoteEventStoreError> {
+        if self.ids.get(event.id()).is_some() {
+            return Err(NoteEventStoreError::DuplicateId(event.id().to_string()));
+        }
+        NoteEventStoreError::check_range(event.id(), event.start_ticks(), event.end_ticks())?;
+
+        let id = self.ids.intern(event.id());
+        self.start_ticks_index
+            .entry(event.start_ticks())
+            .or_default()
+            .insert(id);
+        self.end_ticks_index
+            .entry(event.end_ticks())
+            .or_default()
+            .insert(id);
+        self.track_index
+            .entry(event.track_id())
+            .or_default()
+            .insert(id);
+        if let Some(note_number) = event.note_number() {
+            self.pitch_index.entry(note_number).or_default().insert(id);
+        }
+        *self.durations.entry(duration(&event)).or_default() += 1;
+        self.events.insert(id, event);
+        Ok(())
+    }
+
+    /// Replaces the event with the same id, returning the previous one.
+    pub fn replace_event(&mut self, event: E) -> Result<E, NoteEventStoreError> {
+        let Some(id) = self.ids.get(event.id()) else {
+            return Err(NoteEventStoreError::NotFound(event.id().to_string()));
+        };
+        NoteEventStoreError::check_range(event.id(), event.start_ticks(), event.end_ticks())?;
+
+        Ok(self.modify_event(id, |existing_event| mem::replace(existing_event, event)))
+    }
 
-        let start_ticks_set = self
+    pub fn delete_event(&mut self, id: &str) -> Result<E, NoteEventStoreError> {
+        let Some((id, event)) = self
+            .ids
+            .remove(id)
+            .and_then(|id| self.events.remove_entry(&id))
+        else {
+            return Err(NoteEventStoreError::NotFound(id.to_string()));
+        };
+
+        Self::remove_from_index(&mut self.start_ticks_index, event.start_ticks(), id);
+        Self::remove_from_index(&mut self.end_ticks_index, event.end_ticks(), id);
+        Self::remove_from_index(&mut self.track_index, event.track_id(), id);
+        if let Some(note_number) = event.note_number() {
+            Self::remove_from_index(&mut self.pitch_index, note_number, id);
+        }
+        Self::remove_duration(&mut self.durations, duration(&event));
+
+        Ok(event)
+    }
+
+    pub fn get_event(&self, id: &str) -> Option<&E> {
+        self.events.get(&self.ids.get(id)?)
+    }
+
+    pub fn iter_events(&self) -> impl Iterator<Item = &E> {
+        self.events.values()
+    }
+
+    /// Yields events starting within `start_ticks..=end_ticks`, by start ticks
+    /// and then by `compare`. For point events, which can't overlap the range
+    /// without starting in it, this is a range query that only reads the
+    /// start index.
+    pub fn iter_events_starting_in_range_by<'a>(
+        &'a self,
+        start_ticks: u64,
+        end_ticks: u64,
+        compare: impl Fn(&E, &E) -> Ordering + Copy + 'a,
+    ) -> impl DoubleEndedIterator<Item = &'a E> {
+        // `BTreeMap::range` panics on an inverted range, which matches nothing.
+        self.start_ticks_index
+            .range(start_ticks..=end_ticks.max(start_ticks))
+            .filter(move |(ticks, _)| **ticks <= end_ticks)
+            .flat_map(move |(_, ids)| {
+                let mut events: Vec<&E> = ids.iter().filter_map(|id| self.events.get(id)).collect();
+                events.sort_unstable_by(|a, b| compare(a, b));
+                events
+            })
+    }
+
+    /// Yields events overlapping `start_ticks..=end_ticks` in no particular
+    /// order.
+    pub fn iter_events_by_range(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &E> {
+        // `BTreeMap::range` panics on an inverted range, which is still a valid
+        // query for events spanning the gap between the two ticks.
+        let started_in_range_events_iter = self
             .start_ticks_index
-            .entry(start_ticks)
-            .or_insert(HashSet::new());
-        start_ticks_set.insert(id.clone());
+            .range(start_ticks..=end_ticks.max(start_ticks))
+            .filter(move |(ticks, _)| **ticks <= end_ticks)
+            .flat_map(|(_, ids)| ids.iter().filter_map(|id| self.events.get(id)));
 
-        let end_ticks_set = self
+        // Events that started before the range can only be found through their
+        // end ticks, and never overlap with the ones found above.
+        let started_before_range_events_iter = self
             .end_ticks_index
-            .entry(end_ticks)
-            .or_insert(HashSet::new());
-        end_ticks_set.insert(id);
+            .range(start_ticks..)
+            .flat_map(|(_, ids)| ids.iter().filter_map(|id| self.events.get(id)))
+            .filter(move |event| {
+                event.start_ticks() < start_ticks && event.start_ticks() <= end_ticks
+            });
+
+        started_in_range_events_iter.chain(started_before_range_events_iter)
+    }
+
+    pub fn iter_events_on_track(&self, track_id: u16) -> impl Iterator<Item = &E> {
+        self.track_index
+            .get(&track_id)
+            .into_iter()
+            .flat_map(|ids| ids.iter().filter_map(|id| self.events.get(id)))
     }
 
-    fn add_events(&mut self, events: Vec<NoteEvent>) {
-        for event in events {
-            self.add_event(event);
+    /// Yields events whose `TimedEvent::note_number` is in
+    /// `low_note..=high_note`, in no particular order.
+    pub fn iter_events_by_pitch_range(
+        &self,
+        low_note: u8,
+        high_note: u8,
+    ) -> impl Iterator<Item = &E> {
+        // `BTreeMap::range` panics on an inverted range, which matches nothing.
+        self.pitch_index
+            .range(low_note..=high_note.max(low_note))
+            .filter(move |(note_number, _)| **note_number <= high_note)
+            .flat_map(|(_, ids)| ids.iter().filter_map(|id| self.events.get(id)))
+    }
+
+    // Whether the pitch lanes hold fewer events than a time range query is
+    // likely to touch, taken as a quarter of all events.
+    fn is_pitch_range_selective(&self, low_note: u8, high_note: u8) -> bool {
+        let events_in_pitch_range: usize = self
+            .pitch_index
+            .range(low_note..=high_note.max(low_note))
+            .map(|(_, ids)| ids.len())
+            .sum();
+        events_in_pitch_range * 4 < self.events.len()
+    }
+
+    /// Yields events with `start_ticks <= ticks < end_ticks` in no particular
+    /// order.
+    pub fn iter_events_at(&self, ticks: u64) -> impl Iterator<Item = &E> {
+        // Nothing sounding at `ticks` starts more than the longest duration
+        // before it, so only that window of the start index is scanned.
+        let max_duration = self.durations.last_key_value().map_or(0, |(d, _)| *d);
+        self.start_ticks_index
+            .range(ticks.saturating_sub(max_duration)..=ticks)
+            .flat_map(|(_, ids)| ids.iter().filter_map(|id| self.events.get(id)))
+            .filter(move |event| ticks < event.end_ticks())
+    }
+
+    // The caller must make sure `f` leaves the id alone and the range valid.
+    fn modify_event<T>(&mut self, id: NoteEventId, f: impl FnOnce(&mut E) -> T) -> T {
+        let event = self
+            .events
+            .get_mut(&id)
+            .expect("interned ids always have an event");
+        let (old_start_ticks, old_end_ticks, old_track_id, old_note_number) = (
+            event.start_ticks(),
+            event.end_ticks(),
+            event.track_id(),
+            event.note_number(),
+        );
+
+        let result = f(event);
+
+        if event.start_ticks() != old_start_ticks {
+            Self::remove_from_index(&mut self.start_ticks_index, old_start_ticks, id);
+            self.start_ticks_index
+                .entry(event.start_ticks())
+                .or_default()
+                .insert(id);
+        }
+        if event.end_ticks() != old_end_ticks {
+            Self::remove_from_index(&mut self.end_ticks_index, old_end_ticks, id);
+            self.end_ticks_index
+                .entry(event.end_ticks())
+                .or_default()
+                .insert(id);
         }
+        let (old_duration, new_duration) = (old_end_ticks - old_start_ticks, duration(event));
+        if new_duration != old_duration {
+            Self::remove_duration(&mut self.durations, old_duration);
+            *self.durations.entry(new_duration).or_default() += 1;
+        }
+        if event.track_id() != old_track_id {
+            Self::remove_from_index(&mut self.track_index, old_track_id, id);
+            self.track_index
+                .entry(event.track_id())
+                .or_default()
+                .insert(id);
+        }
+        if event.note_number() != old_note_number {
+            if let Some(note_number) = old_note_number {
+                Self::remove_from_index(&mut self.pitch_index, note_number, id);
+            }
+            if let Some(note_number) = event.note_number() {
+                self.pitch_index.entry(note_number).or_default().insert(id);
+            }
+        }
+        result
     }
 
-    fn update_event(&mut self, event: NoteEventUpdate) {
-        if let Some(existing_event) = self.events.get_mut(&event.id) {
-            if let Some(start_ticks) = event.start_ticks {
-                if let Some(existing_start_ticks_set) =
-                    self.start_ticks_index.get_mut(&existing_event.start_ticks)
-                {
-                    existing_start_ticks_set.remove(&event.id);
-                    if existing_start_ticks_set.is_empty() {
-                        self.start_ticks_index.remove(&existing_event.start_ticks);
-                    }
-                }
-
-                let new_start_ticks_set = self
-                    .start_ticks_index
-                    .entry(start_ticks)
-                    .or_insert(HashSet::new());
-                new_start_ticks_set.insert(event.id.clone());
+    fn remove_duration(durations: &mut BTreeMap<u64, usize>, duration: u64) {
+        if let Some(count) = durations.get_mut(&duration) {
+            *count -= 1;
+            if *count == 0 {
+                durations.remove(&duration);
+            }
+        }
+    }
 
-                existing_event.start_ticks = start_ticks;
+    fn remove_from_index<K: Ord>(
+        index: &mut BTreeMap<K, HashSet<NoteEventId>>,
+        key: K,
+        id: NoteEventId,
+    ) {
+        if let Some(ids) = index.get_mut(&key) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                index.remove(&key);
             }
+        }
+    }
+}
 
-            if let Some(end_ticks) = event.end_ticks {
-                if let Some(existing_end_ticks_set) =
-                    self.end_ticks_index.get_mut(&existing_event.end_ticks)
-                {
-                    existing_end_ticks_set.remove(&event.id);
-                    if existing_end_ticks_set.is_empty() {
-                        self.end_ticks_index.remove(&existing_event.end_ticks);
-                    }
-                }
-
-                let new_end_ticks_set = self
-                    .end_ticks_index
-                    .entry(end_ticks)
-                    .or_insert(HashSet::new());
-                new_end_ticks_set.insert(event.id.clone());
+fn duration(event: &impl TimedEvent) -> u64 {
+    event.end_ticks() - event.start_ticks()
+}
+
+impl NoteEventStore for BTreeNoteEventStore {
+    fn add_event(&mut self, event: NoteEvent) -> Result<(), NoteEventStoreError> {
+        BTreeEventStore::add_event(self, event)
+    }
 
+    fn update_event(&mut self, event: NoteEventUpdate) -> Result<(), NoteEventStoreError> {
+        let Some(id) = self.ids.get(&event.id) else {
+            return Err(NoteEventStoreError::NotFound(event.id));
+        };
+        let existing_event = &self.events[&id];
+
+        NoteEventStoreError::check_range(
+            &event.id,
+            event.start_ticks.unwrap_or(existing_event.start_ticks),
+            event.end_ticks.unwrap_or(existing_event.end_ticks),
+        )?;
+
+        self.modify_event(id, |existing_event| {
+            if let Some(start_ticks) = event.start_ticks {
+                existing_event.start_ticks = start_ticks;
+            }
+            if let Some(end_ticks) = event.end_ticks {
                 existing_event.end_ticks = end_ticks;
             }
-
             if let Some(note_number) = event.note_number {
                 existing_event.note_number = note_number;
             }
-
             if let Some(velocity) = event.velocity {
                 existing_event.velocity = velocity;
             }
-        }
+            if let Some(channel) = event.channel {
+                existing_event.channel = channel;
+            }
+            if let Some(track_id) = event.track_id {
+                existing_event.track_id = track_id;
+            }
+        });
+        Ok(())
     }
 
-    fn update_events(&mut self, events: Vec<NoteEventUpdate>) {
-        for event in events {
-            self.update_event(event);
-        }
+    fn delete_event(&mut self, id: &str) -> Result<NoteEvent, NoteEventStoreError> {
+        BTreeEventStore::delete_event(self, id)
     }
 
-    fn delete_event(&mut self, id: &str) {
-        if let Some(event) = self.events.remove(id) {
-            let start_ticks = event.start_ticks;
-            let end_ticks = event.end_ticks;
+    fn get_event(&self, id: &str) -> Option<&NoteEvent> {
+        BTreeEventStore::get_event(self, id)
+    }
 
-            if let Some(start_ticks_set) = self.start_ticks_index.get_mut(&start_ticks) {
-                start_ticks_set.remove(id);
-                if start_ticks_set.is_empty() {
-                    self.start_ticks_index.remove(&start_ticks);
-                }
-            }
+    fn iter_events(&self) -> impl Iterator<Item = &NoteEvent> {
+        BTreeEventStore::iter_events(self)
+    }
 
-            if let Some(end_ticks_set) = self.end_ticks_index.get_mut(&end_ticks) {
-                end_ticks_set.remove(id);
-                if end_ticks_set.is_empty() {
-                    self.end_ticks_index.remove(&end_ticks);
-                }
-            }
-        }
+    fn iter_events_by_range(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        BTreeEventStore::iter_events_by_range(self, start_ticks, end_ticks)
     }
 
-    fn delete_events(&mut self, ids: Vec<&str>) {
-        for id in ids {
-            self.delete_event(id);
-        }
+    fn iter_events_on_track(&self, track_id: u16) -> impl Iterator<Item = &NoteEvent> {
+        BTreeEventStore::iter_events_on_track(self, track_id)
     }
 
-    fn get_event(&self, id: &str) -> Option<&NoteEvent> {
-        self.events.get(id)
+    fn iter_events_by_pitch_range(
+        &self,
+        low_note: u8,
+        high_note: u8,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        BTreeEventStore::iter_events_by_pitch_range(self, low_note, high_note)
     }
 
-    fn get_events_by_range(&self, start_ticks: u64, end_ticks: u64) -> Vec<&NoteEvent> {
-        let got_event_ids: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+    fn iter_events_in_region(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+        low_note: u8,
+        high_note: u8,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        let by_pitch = self.is_pitch_range_selective(low_note, high_note);
+
+        let pitch_lane_events = by_pitch.then(|| {
+            BTreeEventStore::iter_events_by_pitch_range(self, low_note, high_note)
+                .filter(move |e| RangeSemantics::Closed.overlaps(e, start_ticks, end_ticks))
+        });
+        let time_range_events = (!by_pitch).then(|| {
+            BTreeEventStore::iter_events_by_range(self, start_ticks, end_ticks)
+                .filter(move |e| (low_note..=high_note).contains(&e.note_number))
+        });
+
+        pitch_lane_events
+            .into_iter()
+            .flatten()
+            .chain(time_range_events.into_iter().flatten())
+    }
 
-        let hit_by_start_ticks_events_iter = self
+    fn next_event_after(&self, ticks: u64) -> Option<&NoteEvent> {
+        let (_, ids) = self
             .start_ticks_index
-            .range(start_ticks..=end_ticks)
-            .flat_map(|(_, ids)| {
-                ids.iter().filter_map(|id| {
-                    got_event_ids.borrow_mut().insert(id.clone());
-                    self.events.get(id)
-                })
-            });
+            .range((Bound::Excluded(ticks), Bound::Unbounded))
+            .next()?;
+        ids.iter()
+            .filter_map(|id| self.events.get(id))
+            .min_by(|a, b| NoteEventOrder::StartTicks.compare(a, b))
+    }
 
-        let hit_by_end_ticks_events_iter =
-            self.end_ticks_index
-                .range(start_ticks..)
-                .flat_map(|(_, ids)| {
-                    ids.iter().filter_map(|id| {
-                        if let Some(event) = self.events.get(id) {
-                            if event.start_ticks <= end_ticks
-                                && !got_event_ids.borrow().contains(id)
-                            {
-                                return Some(event);
-                            }
-                        }
-                        None
-                    })
-                });
-
-        hit_by_start_ticks_events_iter
-            .chain(hit_by_end_ticks_events_iter)
-            .collect()
+    fn prev_event_before(&self, ticks: u64) -> Option<&NoteEvent> {
+        let (_, ids) = self.start_ticks_index.range(..ticks).next_back()?;
+        ids.iter()
+            .filter_map(|id| self.events.get(id))
+            .max_by(|a, b| NoteEventOrder::StartTicks.compare(a, b))
+    }
+
+    fn next_event_with_pitch(&self, ticks: u64, note_number: u8) -> Option<&NoteEvent> {
+        self.start_ticks_index
+            .range((Bound::Excluded(ticks), Bound::Unbounded))
+            .find_map(|(_, ids)| {
+                ids.iter()
+                    .filter_map(|id| self.events.get(id))
+                    .filter(|e| e.note_number == note_number)
+                    .min_by(|a, b| NoteEventOrder::StartTicks.compare(a, b))
+            })
+    }
+
+    fn iter_events_at(&self, ticks: u64) -> impl Iterator<Item = &NoteEvent> {
+        BTreeEventStore::iter_events_at(self, ticks)
     }
 }
 
@@ -179,6 +427,20 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_note_event_ids() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::add_events(&mut store);
+
+        let id = store.note_event_id("1").unwrap();
+        assert_eq!(store.get_event_by_note_event_id(id).unwrap().id, "1");
+
+        store.delete_event("1").unwrap();
+
+        assert_eq!(store.note_event_id("1"), None);
+        assert_eq!(store.get_event_by_note_event_id(id), None);
+    }
+
     #[test]
     fn test_add_event() {
         let mut store = BTreeNoteEventStore::new();
@@ -191,6 +453,18 @@ mod tests {
         test_util::add_events(&mut store);
     }
 
+    #[test]
+    fn test_add_event_duplicate_id() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::add_event_duplicate_id(&mut store);
+    }
+
+    #[test]
+    fn test_add_event_invalid_range() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::add_event_invalid_range(&mut store);
+    }
+
     #[test]
     fn test_update_event_all_fields() {
         let mut store = BTreeNoteEventStore::new();
@@ -215,6 +489,12 @@ mod tests {
         test_util::update_event_not_found(&mut store);
     }
 
+    #[test]
+    fn test_update_event_invalid_range() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::update_event_invalid_range(&mut store);
+    }
+
     #[test]
     fn test_update_events() {
         let mut store = BTreeNoteEventStore::new();
@@ -239,15 +519,334 @@ mod tests {
         test_util::delete_events(&mut store);
     }
 
+    #[test]
+    fn test_delete_events_on_track() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::delete_events_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_add_events_atomic() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::add_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_update_events_atomic() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::update_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events_atomic() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::delete_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::transaction(&mut store);
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::transaction_rollback(&mut store);
+    }
+
     #[test]
     fn test_get_event() {
         let mut store = BTreeNoteEventStore::new();
         test_util::get_event(&mut store);
     }
 
+    #[test]
+    fn test_iter_events() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::iter_events(&mut store);
+    }
+
     #[test]
     fn test_get_events_by_range() {
         let mut store = BTreeNoteEventStore::new();
         test_util::get_events_by_range(&mut store);
     }
+
+    #[test]
+    fn test_get_events_by_range_ordered() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::get_events_by_range_ordered(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::get_events_by_range_with(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_on_track() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::get_events_by_range_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_iter_events_by_range() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::iter_events_by_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_random() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::get_events_by_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::get_events_at(&mut store);
+    }
+
+    #[test]
+    fn test_next_and_prev_event() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::next_and_prev_event(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_pitch() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::get_events_by_pitch(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::aggregates_in_range(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range_random() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::aggregates_in_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_query() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::query(&mut store);
+    }
+
+    #[test]
+    fn test_query_random() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::query_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::get_events_in_region(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_time_range() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::get_events_by_time_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_bars() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::get_events_in_bars(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at_random() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::get_events_at_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with_random() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::get_events_by_range_with_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region_random() {
+        let mut store = BTreeNoteEventStore::new();
+        test_util::get_events_in_region_random(&mut store);
+    }
+
+    #[test]
+    fn test_region_by_pitch_lane_and_by_time() {
+        let mut store = BTreeNoteEventStore::new();
+        for i in 0..100 {
+            store
+                .add_event(NoteEvent {
+                    id: i.to_string(),
+                    start_ticks: i * 10,
+                    end_ticks: i * 10 + 25,
+                    note_number: if i % 10 == 0 { 36 } else { 60 },
+                    velocity: 100,
+                    channel: 0,
+                    track_id: 0,
+                })
+                .unwrap();
+        }
+
+        assert!(store.is_pitch_range_selective(36, 36));
+        assert!(!store.is_pitch_range_selective(36, 60));
+
+        let ids = |events: Vec<&NoteEvent>| -> Vec<String> {
+            events.iter().map(|e| e.id.clone()).collect()
+        };
+
+        assert_eq!(
+            ids(store.get_events_in_region(100, 200, 36, 36)),
+            vec!["10", "20"]
+        );
+        assert_eq!(
+            ids(store.get_events_in_region(100, 120, 36, 60)),
+            vec!["8", "9", "10", "11", "12"]
+        );
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct AudioClip {
+        id: String,
+        start_ticks: u64,
+        end_ticks: u64,
+        path: String,
+    }
+
+    impl TimedEvent for AudioClip {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn start_ticks(&self) -> u64 {
+            self.start_ticks
+        }
+
+        fn end_ticks(&self) -> u64 {
+            self.end_ticks
+        }
+    }
+
+    fn audio_clip(id: &str, start_ticks: u64, end_ticks: u64) -> AudioClip {
+        AudioClip {
+            id: id.to_string(),
+            start_ticks,
+            end_ticks,
+            path: format!("{}.wav", id),
+        }
+    }
+
+    #[test]
+    fn test_generic_events() {
+        let mut store = BTreeEventStore::new();
+        store.add_event(audio_clip("a", 0, 100)).unwrap();
+        store.add_event(audio_clip("b", 50, 150)).unwrap();
+
+        assert_eq!(
+            store.add_event(audio_clip("a", 0, 10)),
+            Err(NoteEventStoreError::DuplicateId("a".to_string()))
+        );
+
+        let ids = |store: &BTreeEventStore<AudioClip>, start_ticks, end_ticks| {
+            let mut ids: Vec<String> = store
+                .iter_events_by_range(start_ticks, end_ticks)
+                .map(|clip| clip.id().to_string())
+                .collect();
+            ids.sort_unstable();
+            ids
+        };
+
+        assert_eq!(ids(&store, 120, 200), vec!["b"]);
+        assert_eq!(ids(&store, 60, 60), vec!["a", "b"]);
+
+        let replaced = store.replace_event(audio_clip("b", 200, 300)).unwrap();
+
+        assert_eq!(replaced, audio_clip("b", 50, 150));
+        assert_eq!(ids(&store, 120, 200), vec!["b"]);
+        assert_eq!(ids(&store, 60, 60), vec!["a"]);
+        assert_eq!(
+            store.replace_event(audio_clip("b", 20, 10)),
+            Err(NoteEventStoreError::InvalidRange {
+                id: "b".to_string(),
+                start_ticks: 20,
+                end_ticks: 10,
+            })
+        );
+        assert_eq!(
+            store.replace_event(audio_clip("c", 0, 10)),
+            Err(NoteEventStoreError::NotFound("c".to_string()))
+        );
+
+        assert_eq!(store.delete_event("a").unwrap().path, "a.wav");
+        assert_eq!(store.get_event("a"), None);
+        assert_eq!(store.iter_events().count(), 1);
+    }
+
+    #[test]
+    fn test_get_events_at_after_changing_durations() {
+        let mut store = BTreeNoteEventStore::new();
+        for (id, start_ticks, end_ticks) in [("0", 0, 1000), ("1", 0, 1000), ("2", 900, 950)] {
+            store
+                .add_event(NoteEvent {
+                    id: id.to_string(),
+                    start_ticks,
+                    end_ticks,
+                    note_number: 60,
+                    velocity: 100,
+                    channel: 0,
+                    track_id: 0,
+                })
+                .unwrap();
+        }
+        let ids = |store: &BTreeNoteEventStore, ticks: u64| -> Vec<String> {
+            store
+                .get_events_at(ticks)
+                .iter()
+                .map(|e| e.id.clone())
+                .collect()
+        };
+
+        assert_eq!(ids(&store, 920), vec!["0", "1", "2"]);
+
+        store.delete_event("0").unwrap();
+        assert_eq!(ids(&store, 920), vec!["1", "2"]);
+
+        store
+            .update_event(NoteEventUpdate {
+                id: "1".to_string(),
+                start_ticks: None,
+                end_ticks: Some(10),
+                note_number: None,
+                velocity: None,
+                channel: None,
+                track_id: None,
+            })
+            .unwrap();
+        assert_eq!(store.durations.last_key_value(), Some((&50, &1)));
+        assert_eq!(ids(&store, 920), vec!["2"]);
+        assert_eq!(ids(&store, 5), vec!["1"]);
+
+        store
+            .update_event(NoteEventUpdate {
+                id: "2".to_string(),
+                start_ticks: Some(100),
+                end_ticks: None,
+                note_number: None,
+                velocity: None,
+                channel: None,
+                track_id: None,
+            })
+            .unwrap();
+        assert_eq!(ids(&store, 120), vec!["2"]);
+    }
 }
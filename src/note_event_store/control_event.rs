@@ -0,0 +1,192 @@
+use std::cmp::Ordering;
+
+use super::{btree::BTreeEventStore, event::TimedEvent};
+
+/// A channel message other than a note. Variants are ordered the way messages
+/// on the same tick are sent, so bank selects precede program changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ControlMessage {
+    ControlChange {
+        controller: u8,
+        value: u8,
+    },
+    ProgramChange(u8),
+    ChannelPressure(u8),
+    PolyAftertouch {
+        note_number: u8,
+        pressure: u8,
+    },
+    /// 14-bit value from 0 to 16383, centered at `PITCH_BEND_CENTER`.
+    PitchBend(u16),
+}
+
+pub const PITCH_BEND_CENTER: u16 = 8192;
+
+/// A message that happens at a single tick rather than spanning a range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlEvent {
+    pub id: String,
+    pub ticks: u64,
+    pub message: ControlMessage,
+    /// MIDI channel from 0 to 15.
+    pub channel: u8,
+    pub track_id: u16,
+}
+
+impl ControlEvent {
+    /// Orders by `ticks`, then `message`, then `id`, which is the order the
+    /// events are sent in.
+    pub fn compare(&self, other: &ControlEvent) -> Ordering {
+        (self.ticks, self.message, &self.id).cmp(&(other.ticks, other.message, &other.id))
+    }
+}
+
+impl TimedEvent for ControlEvent {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn start_ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    fn end_ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    fn track_id(&self) -> u16 {
+        self.track_id
+    }
+
+    fn note_number(&self) -> Option<u8> {
+        match self.message {
+            ControlMessage::PolyAftertouch { note_number, .. } => Some(note_number),
+            _ => None,
+        }
+    }
+}
+
+pub type ControlEventStore = BTreeEventStore<ControlEvent>;
+
+impl ControlEventStore {
+    /// Events with `start_ticks <= ticks <= end_ticks`, ordered by
+    /// `ControlEvent::compare`.
+    pub fn get_events_by_range(&self, start_ticks: u64, end_ticks: u64) -> Vec<&ControlEvent> {
+        self.iter_events_starting_in_range_by(start_ticks, end_ticks, ControlEvent::compare)
+            .collect()
+    }
+
+    /// The last event at or before `ticks` matching `predicate`, e.g. to find
+    /// the controller value in effect when playback starts.
+    pub fn last_event_at_or_before(
+        &self,
+        ticks: u64,
+        predicate: impl Fn(&ControlMessage) -> bool,
+    ) -> Option<&ControlEvent> {
+        self.iter_events_starting_in_range_by(u64::MIN, ticks, ControlEvent::compare)
+            .rev()
+            .find(|e| predicate(&e.message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::note_event_store::error::NoteEventStoreError;
+
+    use super::*;
+
+    fn control_event(id: &str, ticks: u64, message: ControlMessage) -> ControlEvent {
+        ControlEvent {
+            id: id.to_string(),
+            ticks,
+            message,
+            channel: 0,
+            track_id: 0,
+        }
+    }
+
+    #[test]
+    fn test_get_events_by_range() {
+        let mut store = ControlEventStore::new();
+        store
+            .add_event(control_event("0", 0, ControlMessage::ProgramChange(5)))
+            .unwrap();
+        store
+            .add_event(control_event(
+                "1",
+                0,
+                ControlMessage::ControlChange {
+                    controller: 0,
+                    value: 1,
+                },
+            ))
+            .unwrap();
+        store
+            .add_event(control_event(
+                "2",
+                10,
+                ControlMessage::PitchBend(PITCH_BEND_CENTER),
+            ))
+            .unwrap();
+        store
+            .add_event(control_event("3", 20, ControlMessage::ChannelPressure(64)))
+            .unwrap();
+
+        let ids = |events: Vec<&ControlEvent>| -> Vec<String> {
+            events.iter().map(|e| e.id.clone()).collect()
+        };
+
+        // The bank select is sent before the program change on the same tick.
+        assert_eq!(ids(store.get_events_by_range(0, 10)), vec!["1", "0", "2"]);
+        assert_eq!(ids(store.get_events_by_range(10, 20)), vec!["2", "3"]);
+        assert!(store.get_events_by_range(11, 19).is_empty());
+
+        assert_eq!(
+            store.add_event(control_event("3", 30, ControlMessage::ProgramChange(0))),
+            Err(NoteEventStoreError::DuplicateId("3".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_last_event_at_or_before() {
+        let mut store = ControlEventStore::new();
+        for (id, ticks, value) in [("0", 0, 10), ("1", 100, 20), ("2", 200, 30)] {
+            store
+                .add_event(control_event(
+                    id,
+                    ticks,
+                    ControlMessage::ControlChange {
+                        controller: 7,
+                        value,
+                    },
+                ))
+                .unwrap();
+        }
+        store
+            .add_event(control_event("3", 150, ControlMessage::ProgramChange(1)))
+            .unwrap();
+
+        let is_volume = |message: &ControlMessage| {
+            matches!(message, ControlMessage::ControlChange { controller: 7, .. })
+        };
+
+        assert_eq!(
+            store
+                .last_event_at_or_before(150, is_volume)
+                .map(|e| e.id.as_str()),
+            Some("1")
+        );
+        assert_eq!(
+            store
+                .last_event_at_or_before(200, is_volume)
+                .map(|e| e.id.as_str()),
+            Some("2")
+        );
+        assert_eq!(
+            store
+                .last_event_at_or_before(99, |m| matches!(m, ControlMessage::ProgramChange(_)))
+                .map(|e| e.id.as_str()),
+            None
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use std::{error::Error, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NoteEventStoreError {
+    DuplicateId(String),
+    NotFound(String),
+    InvalidRange {
+        id: String,
+        start_ticks: u64,
+        end_ticks: u64,
+    },
+    /// Reverting the changes made before `error` failed with
+    /// `rollback_error`, so some of them are still applied.
+    RollbackFailed {
+        error: Box<NoteEventStoreError>,
+        rollback_error: Box<NoteEventStoreError>,
+    },
+}
+
+impl NoteEventStoreError {
+    pub(crate) fn check_range(
+        id: &str,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> Result<(), NoteEventStoreError> {
+        if start_ticks > end_ticks {
+            return Err(NoteEventStoreError::InvalidRange {
+                id: id.to_string(),
+                start_ticks,
+                end_ticks,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn rollback_failed(
+        error: NoteEventStoreError,
+        rollback_error: NoteEventStoreError,
+    ) -> NoteEventStoreError {
+        NoteEventStoreError::RollbackFailed {
+            error: Box::new(error),
+            rollback_error: Box::new(rollback_error),
+        }
+    }
+}
+
+impl fmt::Display for NoteEventStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoteEventStoreError::DuplicateId(id) => {
+                write!(f, "note event with id {:?} already exists", id)
+            }
+            NoteEventStoreError::NotFound(id) => {
+                write!(f, "note event with id {:?} not found", id)
+            }
+            NoteEventStoreError::InvalidRange {
+                id,
+                start_ticks,
+                end_ticks,
+            } => write!(
+                f,
+                "note event with id {:?} has start_ticks {} after end_ticks {}",
+                id, start_ticks, end_ticks
+            ),
+            NoteEventStoreError::RollbackFailed {
+                error,
+                rollback_error,
+            } => write!(f, "{}, and reverting failed: {}", error, rollback_error),
+        }
+    }
+}
+
+impl Error for NoteEventStoreError {}
@@ -1,16 +1,157 @@
-#[derive(Clone)]
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NoteEvent {
     pub id: String,
     pub start_ticks: u64,
     pub end_ticks: u64,
     pub note_number: u8,
     pub velocity: u8,
+    /// MIDI channel from 0 to 15.
+    pub channel: u8,
+    pub track_id: u16,
+}
+
+/// Anything with an id and a span of ticks, which is all a store needs to
+/// index it.
+pub trait TimedEvent {
+    fn id(&self) -> &str;
+    fn start_ticks(&self) -> u64;
+    fn end_ticks(&self) -> u64;
+
+    fn track_id(&self) -> u16 {
+        0
+    }
+
+    /// The pitch the event belongs to, if any.
+    fn note_number(&self) -> Option<u8> {
+        None
+    }
+}
+
+impl TimedEvent for NoteEvent {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn start_ticks(&self) -> u64 {
+        self.start_ticks
+    }
+
+    fn end_ticks(&self) -> u64 {
+        self.end_ticks
+    }
+
+    fn track_id(&self) -> u16 {
+        self.track_id
+    }
+
+    fn note_number(&self) -> Option<u8> {
+        Some(self.note_number)
+    }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NoteEventUpdate {
     pub id: String,
     pub start_ticks: Option<u64>,
     pub end_ticks: Option<u64>,
     pub note_number: Option<u8>,
     pub velocity: Option<u8>,
+    pub channel: Option<u8>,
+    pub track_id: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NoteEventOrder {
+    /// By `start_ticks`, then `note_number`, then `id`.
+    #[default]
+    StartTicks,
+    /// By `end_ticks`, then `note_number`, then `id`.
+    EndTicks,
+}
+
+impl NoteEventOrder {
+    pub fn compare(&self, a: &NoteEvent, b: &NoteEvent) -> Ordering {
+        match self {
+            NoteEventOrder::StartTicks => {
+                (a.start_ticks, a.note_number, &a.id).cmp(&(b.start_ticks, b.note_number, &b.id))
+            }
+            NoteEventOrder::EndTicks => {
+                (a.end_ticks, a.note_number, &a.id).cmp(&(b.end_ticks, b.note_number, &b.id))
+            }
+        }
+    }
+}
+
+/// How an event's ticks are matched against a `start_ticks`/`end_ticks`
+/// range query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RangeSemantics {
+    /// `[start, end]` against the query's `[start, end]`, so events touching
+    /// either end of the range are included.
+    #[default]
+    Closed,
+    /// `[start, end)` against the query's `[start, end)`, so back-to-back
+    /// events are excluded. Zero-length events match if they start within
+    /// the range.
+    HalfOpen,
+    /// The event and the range must share a span of positive length, so
+    /// zero-length events and ranges never match.
+    StrictlyOverlapping,
+}
+
+impl RangeSemantics {
+    pub fn overlaps(&self, event: &NoteEvent, start_ticks: u64, end_ticks: u64) -> bool {
+        match self {
+            RangeSemantics::Closed => {
+                event.start_ticks <= end_ticks && event.end_ticks >= start_ticks
+            }
+            RangeSemantics::HalfOpen => {
+                if event.start_ticks == event.end_ticks {
+                    start_ticks <= event.start_ticks && event.start_ticks < end_ticks
+                } else {
+                    event.start_ticks < end_ticks && event.end_ticks > start_ticks
+                }
+            }
+            RangeSemantics::StrictlyOverlapping => {
+                event.start_ticks.max(start_ticks) < event.end_ticks.min(end_ticks)
+            }
+        }
+    }
+}
+
+/// Velocities of the events matched by an aggregate query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VelocityStats {
+    pub count: usize,
+    pub min: u8,
+    pub max: u8,
+    pub sum: u64,
+}
+
+impl VelocityStats {
+    /// `None` when `velocities` is empty.
+    pub fn from_velocities(velocities: impl IntoIterator<Item = u8>) -> Option<Self> {
+        velocities.into_iter().fold(None, |stats, velocity| {
+            Some(match stats {
+                None => VelocityStats {
+                    count: 1,
+                    min: velocity,
+                    max: velocity,
+                    sum: velocity as u64,
+                },
+                Some(stats) => VelocityStats {
+                    count: stats.count + 1,
+                    min: stats.min.min(velocity),
+                    max: stats.max.max(velocity),
+                    sum: stats.sum + velocity as u64,
+                },
+            })
+        })
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.sum as f64 / self.count as f64
+    }
 }
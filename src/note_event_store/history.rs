@@ -0,0 +1,917 @@
+use super::{
+    base::NoteEventStore,
+    error::NoteEventStoreError,
+    event::{NoteEvent, NoteEventUpdate, VelocityStats},
+    operation::NoteEventOperation,
+    transaction::NoteEventTransaction,
+};
+
+// Each history entry holds the inverse operations of one user action, in the
+// order they were recorded; undoing the entry applies them in reverse.
+type HistoryEntry = Vec<NoteEventOperation>;
+
+pub struct HistoryNoteEventStore<S: NoteEventStore> {
+    store: S,
+    undo_stack: Vec<HistoryEntry>,
+    redo_stack: Vec<HistoryEntry>,
+    // Operations recorded while a group is open, and where each open group's
+    // operations start. The outermost group becomes one entry when it ends.
+    group: HistoryEntry,
+    group_marks: Vec<usize>,
+}
+
+impl<S: NoteEventStore> HistoryNoteEventStore<S> {
+    pub fn new(store: S) -> Self {
+        HistoryNoteEventStore {
+            store,
+            undo_stack: vec![],
+            redo_stack: vec![],
+            group: vec![],
+            group_marks: vec![],
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.store
+    }
+
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn clear_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    /// Reverts the most recent action. Returns `false` if there was nothing to
+    /// undo. If reverting fails, the action is left applied and can still be
+    /// undone. Should putting it back fail as well, the error is
+    /// `NoteEventStoreError::RollbackFailed` and the part that was reverted
+    /// can be redone.
+    pub fn undo(&mut self) -> Result<bool, NoteEventStoreError> {
+        let Some(entry) = self.undo_stack.pop() else {
+            return Ok(false);
+        };
+        let (entry, inverse_entry, result) = self.replay(entry);
+        push_unless_empty(&mut self.undo_stack, entry);
+        push_unless_empty(&mut self.redo_stack, inverse_entry);
+        result.map(|()| true)
+    }
+
+    /// Re-applies the most recently undone action. Returns `false` if there was
+    /// nothing to redo. If re-applying fails, the action is left undone and can
+    /// still be redone. Should undoing it again fail as well, the error is
+    /// `NoteEventStoreError::RollbackFailed` and the part that was re-applied
+    /// can be undone.
+    pub fn redo(&mut self) -> Result<bool, NoteEventStoreError> {
+        let Some(entry) = self.redo_stack.pop() else {
+            return Ok(false);
+        };
+        let (entry, inverse_entry, result) = self.replay(entry);
+        push_unless_empty(&mut self.redo_stack, entry);
+        push_unless_empty(&mut self.undo_stack, inverse_entry);
+        result.map(|()| true)
+    }
+
+    /// Records every operation performed by `f` as a single history entry, so
+    /// one `undo` reverts all of them. If `f` fails, its operations are
+    /// reverted and nothing is recorded, unless reverting fails too; the
+    /// operations still applied are then recorded so they can be undone later.
+    /// Nested groups join the outermost one.
+    pub fn group<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, NoteEventStoreError>,
+    ) -> Result<T, NoteEventStoreError> {
+        self.begin_transaction();
+        let (result, committed) = match f(self) {
+            Ok(value) => (Ok(value), true),
+            Err(error) => match self.rollback_group() {
+                Ok(()) => (Err(error), false),
+                // The operations left in the group are still applied, so they
+                // are recorded like a successful group.
+                Err(rollback_error) => (
+                    Err(NoteEventStoreError::rollback_failed(error, rollback_error)),
+                    true,
+                ),
+            },
+        };
+        self.end_transaction(committed);
+        result
+    }
+
+    // Reverts the innermost group's operations, newest first, and stops at the
+    // first revert that fails, leaving that operation and the older ones in
+    // the group.
+    fn rollback_group(&mut self) -> Result<(), NoteEventStoreError> {
+        let Some(&mark) = self.group_marks.last() else {
+            return Ok(());
+        };
+        while self.group.len() > mark {
+            let operation = self.group.pop().unwrap();
+            if let Err(error) = operation.clone().apply(&mut self.store) {
+                self.group.push(operation);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    // Applies `entry` in reverse, all or nothing, and returns what is left of
+    // it along with the inverse of what was applied. Only if reverting a
+    // failed replay fails as well is the entry split between the two, so
+    // each applied operation can still be undone or redone.
+    fn replay(
+        &mut self,
+        mut entry: HistoryEntry,
+    ) -> (HistoryEntry, HistoryEntry, Result<(), NoteEventStoreError>) {
+        self.store.begin_transaction();
+        let mut inverse_entry = Vec::with_capacity(entry.len());
+        while let Some(operation) = entry.pop() {
+            let error = match operation.clone().apply(&mut self.store) {
+                Ok(inverse) => {
+                    inverse_entry.push(inverse);
+                    continue;
+                }
+                Err(error) => error,
+            };
+            entry.push(operation);
+
+            while let Some(inverse) = inverse_entry.pop() {
+                match inverse.clone().apply(&mut self.store) {
+                    Ok(operation) => entry.push(operation),
+                    Err(rollback_error) => {
+                        inverse_entry.push(inverse);
+                        self.store.end_transaction(true);
+                        let error = NoteEventStoreError::rollback_failed(error, rollback_error);
+                        return (entry, inverse_entry, Err(error));
+                    }
+                }
+            }
+            self.store.end_transaction(false);
+            return (entry, inverse_entry, Err(error));
+        }
+        self.store.end_transaction(true);
+        (entry, inverse_entry, Ok(()))
+    }
+
+    fn push_entry(&mut self, entry: HistoryEntry) {
+        if entry.is_empty() {
+            return;
+        }
+        self.undo_stack.push(entry);
+        self.redo_stack.clear();
+    }
+
+    fn record(
+        &mut self,
+        operation: NoteEventOperation,
+    ) -> Result<NoteEventOperation, NoteEventStoreError> {
+        let inverse = operation.apply(&mut self.store)?;
+        if self.group_marks.is_empty() {
+            self.push_entry(vec![inverse.clone()]);
+        } else {
+            self.group.push(inverse.clone());
+        }
+        Ok(inverse)
+    }
+}
+
+fn push_unless_empty(stack: &mut Vec<HistoryEntry>, entry: HistoryEntry) {
+    if !entry.is_empty() {
+        stack.push(entry);
+    }
+}
+
+impl<S: NoteEventStore> NoteEventStore for HistoryNoteEventStore<S> {
+    fn add_event(&mut self, event: NoteEvent) -> Result<(), NoteEventStoreError> {
+        self.record(NoteEventOperation::Add(event))?;
+        Ok(())
+    }
+
+    fn update_event(&mut self, event: NoteEventUpdate) -> Result<(), NoteEventStoreError> {
+        self.record(NoteEventOperation::Update(event))?;
+        Ok(())
+    }
+
+    fn delete_event(&mut self, id: &str) -> Result<NoteEvent, NoteEventStoreError> {
+        match self.record(NoteEventOperation::Delete(id.to_string()))? {
+            NoteEventOperation::Add(event) => Ok(event),
+            _ => unreachable!("the inverse of a delete is an add"),
+        }
+    }
+
+    fn get_event(&self, id: &str) -> Option<&NoteEvent> {
+        self.store.get_event(id)
+    }
+
+    fn begin_transaction(&mut self) {
+        self.group_marks.push(self.group.len());
+        self.store.begin_transaction();
+    }
+
+    fn end_transaction(&mut self, committed: bool) {
+        let mark = self
+            .group_marks
+            .pop()
+            .expect("every transaction end has a matching begin");
+        if !committed {
+            // Whoever ran the transaction already reverted these, and the
+            // reverts were recorded right after them.
+            self.group.truncate(mark);
+        }
+        if self.group_marks.is_empty() {
+            let entry = std::mem::take(&mut self.group);
+            self.push_entry(entry);
+        }
+
+        self.store.end_transaction(committed);
+    }
+
+    fn transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut NoteEventTransaction<'_, Self>) -> Result<T, NoteEventStoreError>,
+    ) -> Result<T, NoteEventStoreError> {
+        // The group already reverts failed operations and keeps them out of
+        // the history, so the transaction's own rollback is not needed.
+        self.group(|history| f(&mut NoteEventTransaction::new(history)))
+    }
+
+    fn iter_events(&self) -> impl Iterator<Item = &NoteEvent> {
+        self.store.iter_events()
+    }
+
+    fn iter_events_by_range(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.store.iter_events_by_range(start_ticks, end_ticks)
+    }
+
+    fn iter_events_on_track(&self, track_id: u16) -> impl Iterator<Item = &NoteEvent> {
+        self.store.iter_events_on_track(track_id)
+    }
+
+    fn iter_events_by_range_on_track(
+        &self,
+        track_id: u16,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.store
+            .iter_events_by_range_on_track(track_id, start_ticks, end_ticks)
+    }
+
+    fn count_events_in_range(&self, start_ticks: u64, end_ticks: u64) -> usize {
+        self.store.count_events_in_range(start_ticks, end_ticks)
+    }
+
+    fn max_polyphony_in_range(&self, start_ticks: u64, end_ticks: u64) -> usize {
+        self.store.max_polyphony_in_range(start_ticks, end_ticks)
+    }
+
+    fn pitch_bounds_in_range(&self, start_ticks: u64, end_ticks: u64) -> Option<(u8, u8)> {
+        self.store.pitch_bounds_in_range(start_ticks, end_ticks)
+    }
+
+    fn velocity_stats_in_range(&self, start_ticks: u64, end_ticks: u64) -> Option<VelocityStats> {
+        self.store.velocity_stats_in_range(start_ticks, end_ticks)
+    }
+
+    fn iter_events_at(&self, ticks: u64) -> impl Iterator<Item = &NoteEvent> {
+        self.store.iter_events_at(ticks)
+    }
+
+    fn next_event_after(&self, ticks: u64) -> Option<&NoteEvent> {
+        self.store.next_event_after(ticks)
+    }
+
+    fn prev_event_before(&self, ticks: u64) -> Option<&NoteEvent> {
+        self.store.prev_event_before(ticks)
+    }
+
+    fn next_event_with_pitch(&self, ticks: u64, note_number: u8) -> Option<&NoteEvent> {
+        self.store.next_event_with_pitch(ticks, note_number)
+    }
+
+    fn iter_events_by_pitch_range(
+        &self,
+        low_note: u8,
+        high_note: u8,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.store.iter_events_by_pitch_range(low_note, high_note)
+    }
+
+    fn iter_events_in_region(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+        low_note: u8,
+        high_note: u8,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.store
+            .iter_events_in_region(start_ticks, end_ticks, low_note, high_note)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::note_event_store::{
+        btree::BTreeNoteEventStore,
+        test_util::{self, FailingDeleteNoteEventStore},
+    };
+
+    use super::*;
+
+    fn new_store() -> HistoryNoteEventStore<BTreeNoteEventStore> {
+        HistoryNoteEventStore::new(BTreeNoteEventStore::new())
+    }
+
+    fn new_failing_delete_store() -> (
+        HistoryNoteEventStore<FailingDeleteNoteEventStore>,
+        Rc<RefCell<Option<String>>>,
+    ) {
+        let failing_id = Rc::new(RefCell::new(None));
+        let store =
+            HistoryNoteEventStore::new(FailingDeleteNoteEventStore::new(failing_id.clone()));
+        (store, failing_id)
+    }
+
+    fn note_event(id: &str, start_ticks: u64, end_ticks: u64) -> NoteEvent {
+        NoteEvent {
+            id: id.to_string(),
+            start_ticks,
+            end_ticks,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        }
+    }
+
+    #[test]
+    fn test_add_event() {
+        let mut store = new_store();
+        test_util::add_event(&mut store);
+    }
+
+    #[test]
+    fn test_add_events() {
+        let mut store = new_store();
+        test_util::add_events(&mut store);
+    }
+
+    #[test]
+    fn test_add_event_duplicate_id() {
+        let mut store = new_store();
+        test_util::add_event_duplicate_id(&mut store);
+    }
+
+    #[test]
+    fn test_add_event_invalid_range() {
+        let mut store = new_store();
+        test_util::add_event_invalid_range(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_all_fields() {
+        let mut store = new_store();
+        test_util::update_event_all_fields(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_partial() {
+        let mut store = new_store();
+        test_util::update_event_partial(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_all_none() {
+        let mut store = new_store();
+        test_util::update_event_all_none(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_not_found() {
+        let mut store = new_store();
+        test_util::update_event_not_found(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_invalid_range() {
+        let mut store = new_store();
+        test_util::update_event_invalid_range(&mut store);
+    }
+
+    #[test]
+    fn test_update_events() {
+        let mut store = new_store();
+        test_util::update_events(&mut store);
+    }
+
+    #[test]
+    fn test_delete_event() {
+        let mut store = new_store();
+        test_util::delete_event(&mut store);
+    }
+
+    #[test]
+    fn test_delete_event_not_found() {
+        let mut store = new_store();
+        test_util::delete_event_not_found(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events() {
+        let mut store = new_store();
+        test_util::delete_events(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events_on_track() {
+        let mut store = new_store();
+        test_util::delete_events_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_add_events_atomic() {
+        let mut store = new_store();
+        test_util::add_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_update_events_atomic() {
+        let mut store = new_store();
+        test_util::update_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events_atomic() {
+        let mut store = new_store();
+        test_util::delete_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut store = new_store();
+        test_util::transaction(&mut store);
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        let mut store = new_store();
+        test_util::transaction_rollback(&mut store);
+    }
+
+    #[test]
+    fn test_get_event() {
+        let mut store = new_store();
+        test_util::get_event(&mut store);
+    }
+
+    #[test]
+    fn test_iter_events() {
+        let mut store = new_store();
+        test_util::iter_events(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range() {
+        let mut store = new_store();
+        test_util::get_events_by_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_ordered() {
+        let mut store = new_store();
+        test_util::get_events_by_range_ordered(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with() {
+        let mut store = new_store();
+        test_util::get_events_by_range_with(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_on_track() {
+        let mut store = new_store();
+        test_util::get_events_by_range_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_iter_events_by_range() {
+        let mut store = new_store();
+        test_util::iter_events_by_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_random() {
+        let mut store = new_store();
+        test_util::get_events_by_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at() {
+        let mut store = new_store();
+        test_util::get_events_at(&mut store);
+    }
+
+    #[test]
+    fn test_next_and_prev_event() {
+        let mut store = new_store();
+        test_util::next_and_prev_event(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_pitch() {
+        let mut store = new_store();
+        test_util::get_events_by_pitch(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range() {
+        let mut store = new_store();
+        test_util::aggregates_in_range(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range_random() {
+        let mut store = new_store();
+        test_util::aggregates_in_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_query() {
+        let mut store = new_store();
+        test_util::query(&mut store);
+    }
+
+    #[test]
+    fn test_query_random() {
+        let mut store = new_store();
+        test_util::query_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region() {
+        let mut store = new_store();
+        test_util::get_events_in_region(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_time_range() {
+        let mut store = new_store();
+        test_util::get_events_by_time_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_bars() {
+        let mut store = new_store();
+        test_util::get_events_in_bars(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at_random() {
+        let mut store = new_store();
+        test_util::get_events_at_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with_random() {
+        let mut store = new_store();
+        test_util::get_events_by_range_with_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region_random() {
+        let mut store = new_store();
+        test_util::get_events_in_region_random(&mut store);
+    }
+
+    #[test]
+    fn test_undo_redo_add_event() {
+        let mut store = new_store();
+        store.add_event(note_event("0", 0, 10)).unwrap();
+
+        assert!(store.undo().unwrap());
+        assert!(store.get_event("0").is_none());
+        assert!(!store.can_undo());
+
+        assert!(store.redo().unwrap());
+        assert_eq!(store.get_event("0"), Some(&note_event("0", 0, 10)));
+        assert!(!store.can_redo());
+    }
+
+    #[test]
+    fn test_undo_redo_update_event() {
+        let mut store = new_store();
+        store.add_event(note_event("0", 0, 10)).unwrap();
+        store
+            .update_event(NoteEventUpdate {
+                id: "0".to_string(),
+                start_ticks: Some(5),
+                end_ticks: None,
+                note_number: Some(70),
+                velocity: None,
+                channel: None,
+                track_id: None,
+            })
+            .unwrap();
+        store.undo().unwrap();
+
+        assert_eq!(store.get_event("0"), Some(&note_event("0", 0, 10)));
+
+        store.redo().unwrap();
+        let event = store.get_event("0").unwrap();
+
+        assert_eq!(event.start_ticks, 5);
+        assert_eq!(event.end_ticks, 10);
+        assert_eq!(event.note_number, 70);
+    }
+
+    #[test]
+    fn test_undo_redo_delete_event() {
+        let mut store = new_store();
+        store.add_event(note_event("0", 0, 10)).unwrap();
+        store.delete_event("0").unwrap();
+
+        store.undo().unwrap();
+
+        assert_eq!(store.get_event("0"), Some(&note_event("0", 0, 10)));
+        assert_eq!(store.get_events_by_range(0, 10).len(), 1);
+
+        store.redo().unwrap();
+
+        assert!(store.get_event("0").is_none());
+    }
+
+    #[test]
+    fn test_undo_batch_as_one_step() {
+        let mut store = new_store();
+        store
+            .add_events(vec![note_event("0", 0, 10), note_event("1", 10, 20)])
+            .unwrap();
+        store.delete_events(vec!["0", "1"]).unwrap();
+
+        store.undo().unwrap();
+
+        assert!(store.get_event("0").is_some());
+        assert!(store.get_event("1").is_some());
+
+        store.undo().unwrap();
+
+        assert!(store.iter_events().next().is_none());
+        assert!(!store.undo().unwrap());
+    }
+
+    #[test]
+    fn test_group() {
+        let mut store = new_store();
+        store
+            .group(|store| {
+                store.add_event(note_event("0", 0, 10))?;
+                store.update_event(NoteEventUpdate {
+                    id: "0".to_string(),
+                    start_ticks: None,
+                    end_ticks: Some(20),
+                    note_number: None,
+                    velocity: None,
+                    channel: None,
+                    track_id: None,
+                })?;
+                store.group(|store| store.add_event(note_event("1", 10, 20)))
+            })
+            .unwrap();
+
+        assert_eq!(store.get_event("0").unwrap().end_ticks, 20);
+
+        store.undo().unwrap();
+
+        assert!(store.iter_events().next().is_none());
+        assert!(!store.can_undo());
+
+        store.redo().unwrap();
+
+        assert_eq!(store.get_event("0").unwrap().end_ticks, 20);
+        assert!(store.get_event("1").is_some());
+    }
+
+    #[test]
+    fn test_group_rollback() {
+        let mut store = new_store();
+        store.add_event(note_event("0", 0, 10)).unwrap();
+
+        let result = store.group(|store| {
+            store.delete_event("0")?;
+            store.add_event(note_event("1", 0, 10))?;
+            store.add_event(note_event("1", 0, 10))
+        });
+
+        assert_eq!(
+            result,
+            Err(NoteEventStoreError::DuplicateId("1".to_string()))
+        );
+        assert_eq!(store.get_event("0"), Some(&note_event("0", 0, 10)));
+        assert!(store.get_event("1").is_none());
+
+        store.undo().unwrap();
+
+        assert!(store.iter_events().next().is_none());
+        assert!(!store.can_undo());
+    }
+
+    #[test]
+    fn test_nested_group_rollback() {
+        let mut store = new_store();
+
+        store
+            .group(|store| {
+                store.add_event(note_event("0", 0, 10))?;
+                let result = store.group(|store| {
+                    store.add_event(note_event("1", 0, 10))?;
+                    store.delete_event("2")
+                });
+                assert!(result.is_err());
+                store.add_event(note_event("3", 0, 10))
+            })
+            .unwrap();
+
+        assert!(store.get_event("0").is_some());
+        assert!(store.get_event("1").is_none());
+        assert!(store.get_event("3").is_some());
+
+        store.undo().unwrap();
+
+        assert!(store.iter_events().next().is_none());
+    }
+
+    #[test]
+    fn test_failed_batch_is_not_recorded() {
+        let mut store = new_store();
+        store.add_event(note_event("0", 0, 10)).unwrap();
+
+        assert!(store
+            .add_events(vec![note_event("1", 0, 10), note_event("0", 0, 10)])
+            .is_err());
+        assert!(store.get_event("1").is_none());
+
+        store.undo().unwrap();
+
+        assert!(store.iter_events().next().is_none());
+        assert!(!store.can_undo());
+    }
+
+    #[test]
+    fn test_new_action_clears_redo() {
+        let mut store = new_store();
+        store.add_event(note_event("0", 0, 10)).unwrap();
+        store.undo().unwrap();
+        store.add_event(note_event("1", 0, 10)).unwrap();
+
+        assert!(!store.can_redo());
+        assert!(!store.redo().unwrap());
+    }
+
+    #[test]
+    fn test_failed_operation_is_not_recorded() {
+        let mut store = new_store();
+        store.add_event(note_event("0", 0, 10)).unwrap();
+
+        assert!(store.add_event(note_event("0", 0, 10)).is_err());
+        assert!(store.delete_event("1").is_err());
+
+        store.undo().unwrap();
+
+        assert!(!store.can_undo());
+    }
+
+    #[test]
+    fn test_failed_undo_is_reverted() {
+        let (mut store, failing_id) = new_failing_delete_store();
+        store
+            .add_events(vec![note_event("0", 0, 10), note_event("1", 10, 20)])
+            .unwrap();
+        *failing_id.borrow_mut() = Some("0".to_string());
+
+        // "1" is deleted before deleting "0" fails.
+        assert_eq!(
+            store.undo(),
+            Err(NoteEventStoreError::NotFound("0".to_string()))
+        );
+        assert!(store.get_event("0").is_some());
+        assert!(store.get_event("1").is_some());
+        assert!(store.can_undo());
+        assert!(!store.can_redo());
+
+        *failing_id.borrow_mut() = None;
+        store.undo().unwrap();
+
+        assert!(store.iter_events().next().is_none());
+    }
+
+    #[test]
+    fn test_failed_redo_is_reverted() {
+        let (mut store, failing_id) = new_failing_delete_store();
+        store.add_event(note_event("0", 0, 10)).unwrap();
+        store.add_event(note_event("1", 10, 20)).unwrap();
+        store.delete_events(vec!["1", "0"]).unwrap();
+        store.undo().unwrap();
+        *failing_id.borrow_mut() = Some("0".to_string());
+
+        assert!(store.redo().is_err());
+        assert!(store.get_event("0").is_some());
+        assert!(store.get_event("1").is_some());
+        assert!(store.can_redo());
+
+        *failing_id.borrow_mut() = None;
+        store.redo().unwrap();
+
+        assert!(store.iter_events().next().is_none());
+        assert!(!store.can_redo());
+    }
+
+    #[test]
+    fn test_failed_group_rollback() {
+        let (mut store, failing_id) = new_failing_delete_store();
+        *failing_id.borrow_mut() = Some("1".to_string());
+
+        let result = store.group(|store| {
+            store.add_event(note_event("0", 0, 10))?;
+            store.add_event(note_event("1", 10, 20))?;
+            store.delete_event("2").map(|_| ())
+        });
+
+        assert_eq!(
+            result,
+            Err(NoteEventStoreError::rollback_failed(
+                NoteEventStoreError::NotFound("2".to_string()),
+                NoteEventStoreError::NotFound("1".to_string()),
+            ))
+        );
+        assert!(store.get_event("0").is_some());
+        assert!(store.get_event("1").is_some());
+
+        // Undoing runs into the same failure, and leaves everything in place.
+        assert!(store.undo().is_err());
+        assert!(store.get_event("0").is_some());
+        assert!(store.get_event("1").is_some());
+        assert!(!store.can_redo());
+
+        *failing_id.borrow_mut() = None;
+        store.undo().unwrap();
+
+        assert!(store.iter_events().next().is_none());
+        assert!(!store.can_undo());
+    }
+
+    #[test]
+    fn test_failed_undo_rollback() {
+        let (mut store, failing_id) = new_failing_delete_store();
+        store
+            .group(|store| {
+                store.add_event(note_event("0", 0, 10))?;
+                store.delete_event("0").map(|_| ())
+            })
+            .unwrap();
+        *failing_id.borrow_mut() = Some("0".to_string());
+
+        // Undoing adds "0" back, fails to delete it, and fails to revert.
+        assert_eq!(
+            store.undo(),
+            Err(NoteEventStoreError::rollback_failed(
+                NoteEventStoreError::NotFound("0".to_string()),
+                NoteEventStoreError::NotFound("0".to_string()),
+            ))
+        );
+        assert!(store.get_event("0").is_some());
+        assert!(store.can_undo());
+        assert!(store.can_redo());
+
+        *failing_id.borrow_mut() = None;
+        store.undo().unwrap();
+
+        assert!(store.iter_events().next().is_none());
+        assert!(!store.can_undo());
+
+        store.redo().unwrap();
+
+        assert!(store.get_event("0").is_some());
+
+        store.redo().unwrap();
+
+        assert!(store.iter_events().next().is_none());
+        assert!(!store.can_redo());
+    }
+}
@@ -0,0 +1,131 @@
+use std::{collections::HashMap, fmt, num::ParseIntError, str::FromStr, sync::Arc};
+
+/// A compact, copyable event id for use as a key in place of the string ids
+/// carried by `NoteEvent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NoteEventId(pub u64);
+
+impl From<u64> for NoteEventId {
+    fn from(id: u64) -> Self {
+        NoteEventId(id)
+    }
+}
+
+impl From<NoteEventId> for u64 {
+    fn from(id: NoteEventId) -> Self {
+        id.0
+    }
+}
+
+impl fmt::Display for NoteEventId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Parses numeric string ids such as `"42"`. Use `NoteEventIdInterner` for
+/// arbitrary strings.
+impl FromStr for NoteEventId {
+    type Err = ParseIntError;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        id.parse().map(NoteEventId)
+    }
+}
+
+/// Maps string ids to `NoteEventId`s and back. Each string is stored once and
+/// shared by both directions of the mapping.
+#[derive(Debug, Default, Clone)]
+pub struct NoteEventIdInterner {
+    ids: HashMap<Arc<str>, NoteEventId>,
+    names: HashMap<NoteEventId, Arc<str>>,
+    next_id: u64,
+}
+
+impl NoteEventIdInterner {
+    pub fn new() -> Self {
+        NoteEventIdInterner {
+            ids: HashMap::new(),
+            names: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Returns the id for `name`, assigning a new one if it has none yet.
+    pub fn intern(&mut self, name: &str) -> NoteEventId {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+
+        let id = NoteEventId(self.next_id);
+        self.next_id += 1;
+
+        let name: Arc<str> = Arc::from(name);
+        self.ids.insert(name.clone(), id);
+        self.names.insert(id, name);
+        id
+    }
+
+    pub fn get(&self, name: &str) -> Option<NoteEventId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn resolve(&self, id: NoteEventId) -> Option<&str> {
+        self.names.get(&id).map(|name| &**name)
+    }
+
+    /// Forgets `name`. Its id is never handed out again.
+    pub fn remove(&mut self, name: &str) -> Option<NoteEventId> {
+        let id = self.ids.remove(name)?;
+        self.names.remove(&id);
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_event_id_conversions() {
+        let id = NoteEventId::from(42);
+
+        assert_eq!(u64::from(id), 42);
+        assert_eq!(id.to_string(), "42");
+        assert_eq!("42".parse::<NoteEventId>(), Ok(id));
+        assert!("0-1".parse::<NoteEventId>().is_err());
+    }
+
+    #[test]
+    fn test_interner() {
+        let mut interner = NoteEventIdInterner::new();
+
+        let a = interner.intern("a");
+        let b = interner.intern("0-1");
+
+        assert_ne!(a, b);
+        assert_eq!(interner.intern("a"), a);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.get("0-1"), Some(b));
+        assert_eq!(interner.get("c"), None);
+        assert_eq!(interner.resolve(a), Some("a"));
+
+        assert_eq!(interner.remove("a"), Some(a));
+        assert_eq!(interner.remove("a"), None);
+        assert_eq!(interner.resolve(a), None);
+
+        // Removed ids are not reused.
+        let c = interner.intern("a");
+        assert_ne!(c, a);
+        assert_ne!(c, b);
+        assert!(!interner.is_empty());
+    }
+}
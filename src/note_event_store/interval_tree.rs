@@ -0,0 +1,586 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use super::{
+    base::NoteEventStore,
+    error::NoteEventStoreError,
+    event::{NoteEvent, NoteEventUpdate},
+};
+
+type Link = Option<Box<Node>>;
+
+struct Node {
+    start_ticks: u64,
+    end_ticks: u64,
+    id: String,
+    // Largest `end_ticks` in the subtree rooted at this node.
+    max_end_ticks: u64,
+    height: i32,
+    left: Link,
+    right: Link,
+}
+
+impl Node {
+    fn new(start_ticks: u64, end_ticks: u64, id: String) -> Box<Node> {
+        Box::new(Node {
+            start_ticks,
+            end_ticks,
+            id,
+            max_end_ticks: end_ticks,
+            height: 1,
+            left: None,
+            right: None,
+        })
+    }
+
+    fn cmp_key(&self, start_ticks: u64, id: &str) -> Ordering {
+        (start_ticks, id).cmp(&(self.start_ticks, self.id.as_str()))
+    }
+
+    fn update(&mut self) {
+        self.height = 1 + height(&self.left).max(height(&self.right));
+        self.max_end_ticks = self
+            .end_ticks
+            .max(max_end_ticks(&self.left))
+            .max(max_end_ticks(&self.right));
+    }
+}
+
+fn height(link: &Link) -> i32 {
+    link.as_ref().map_or(0, |node| node.height)
+}
+
+fn max_end_ticks(link: &Link) -> u64 {
+    link.as_ref().map_or(0, |node| node.max_end_ticks)
+}
+
+fn rotate_left(mut node: Box<Node>) -> Box<Node> {
+    let mut right = node.right.take().unwrap();
+    node.right = right.left.take();
+    node.update();
+    right.left = Some(node);
+    right.update();
+    right
+}
+
+fn rotate_right(mut node: Box<Node>) -> Box<Node> {
+    let mut left = node.left.take().unwrap();
+    node.left = left.right.take();
+    node.update();
+    left.right = Some(node);
+    left.update();
+    left
+}
+
+fn balance(mut node: Box<Node>) -> Box<Node> {
+    node.update();
+    let balance_factor = height(&node.left) - height(&node.right);
+
+    if balance_factor > 1 {
+        let left = node.left.take().unwrap();
+        node.left = Some(if height(&left.left) < height(&left.right) {
+            rotate_left(left)
+        } else {
+            left
+        });
+        return rotate_right(node);
+    }
+
+    if balance_factor < -1 {
+        let right = node.right.take().unwrap();
+        node.right = Some(if height(&right.right) < height(&right.left) {
+            rotate_right(right)
+        } else {
+            right
+        });
+        return rotate_left(node);
+    }
+
+    node
+}
+
+fn insert(link: Link, new_node: Box<Node>) -> Box<Node> {
+    match link {
+        None => new_node,
+        Some(mut node) => {
+            if node.cmp_key(new_node.start_ticks, &new_node.id) == Ordering::Less {
+                node.left = Some(insert(node.left.take(), new_node));
+            } else {
+                node.right = Some(insert(node.right.take(), new_node));
+            }
+            balance(node)
+        }
+    }
+}
+
+fn remove_min(mut node: Box<Node>) -> (Link, Box<Node>) {
+    match node.left.take() {
+        None => {
+            let right = node.right.take();
+            (right, node)
+        }
+        Some(left) => {
+            let (left, min) = remove_min(left);
+            node.left = left;
+            (Some(balance(node)), min)
+        }
+    }
+}
+
+fn remove(link: Link, start_ticks: u64, id: &str) -> (Link, bool) {
+    let Some(mut node) = link else {
+        return (None, false);
+    };
+
+    match node.cmp_key(start_ticks, id) {
+        Ordering::Less => {
+            let (left, removed) = remove(node.left.take(), start_ticks, id);
+            node.left = left;
+            (Some(balance(node)), removed)
+        }
+        Ordering::Greater => {
+            let (right, removed) = remove(node.right.take(), start_ticks, id);
+            node.right = right;
+            (Some(balance(node)), removed)
+        }
+        Ordering::Equal => match (node.left.take(), node.right.take()) {
+            (None, right) => (right, true),
+            (left, None) => (left, true),
+            (Some(left), Some(right)) => {
+                let (right, mut min) = remove_min(right);
+                min.left = Some(left);
+                min.right = right;
+                (Some(balance(min)), true)
+            }
+        },
+    }
+}
+
+// AVL tree keyed by `(start_ticks, id)` and augmented with the maximum
+// `end_ticks` of every subtree, so overlap queries can skip whole subtrees.
+#[derive(Default)]
+pub(crate) struct IntervalTree {
+    root: Link,
+}
+
+impl IntervalTree {
+    pub(crate) fn new() -> Self {
+        IntervalTree { root: None }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub(crate) fn insert(&mut self, start_ticks: u64, end_ticks: u64, id: String) {
+        self.root = Some(insert(
+            self.root.take(),
+            Node::new(start_ticks, end_ticks, id),
+        ));
+    }
+
+    pub(crate) fn remove(&mut self, start_ticks: u64, id: &str) -> bool {
+        let (root, removed) = remove(self.root.take(), start_ticks, id);
+        self.root = root;
+        removed
+    }
+
+    // Ids of every interval with `start_ticks <= end` and `end_ticks >= start`,
+    // in `(start_ticks, id)` order.
+    pub(crate) fn overlapping(&self, start_ticks: u64, end_ticks: u64) -> Overlapping<'_> {
+        let mut iter = Overlapping {
+            stack: vec![],
+            start_ticks,
+            end_ticks,
+        };
+        iter.push_left(self.root.as_deref());
+        iter
+    }
+}
+
+pub(crate) struct Overlapping<'a> {
+    stack: Vec<&'a Node>,
+    start_ticks: u64,
+    end_ticks: u64,
+}
+
+impl<'a> Overlapping<'a> {
+    fn push_left(&mut self, mut link: Option<&'a Node>) {
+        while let Some(node) = link {
+            if node.max_end_ticks < self.start_ticks {
+                break;
+            }
+            self.stack.push(node);
+            link = node.left.as_deref();
+        }
+    }
+}
+
+impl<'a> Iterator for Overlapping<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            if node.start_ticks > self.end_ticks {
+                // Everything left on the stack starts even later.
+                self.stack.clear();
+                return None;
+            }
+            self.push_left(node.right.as_deref());
+            if node.end_ticks >= self.start_ticks {
+                return Some(&node.id);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Default)]
+pub struct IntervalTreeNoteEventStore {
+    events: HashMap<String, NoteEvent>,
+    tree: IntervalTree,
+}
+
+impl IntervalTreeNoteEventStore {
+    pub fn new() -> Self {
+        IntervalTreeNoteEventStore {
+            events: HashMap::new(),
+            tree: IntervalTree::new(),
+        }
+    }
+}
+
+impl NoteEventStore for IntervalTreeNoteEventStore {
+    fn add_event(&mut self, event: NoteEvent) -> Result<(), NoteEventStoreError> {
+        if self.events.contains_key(&event.id) {
+            return Err(NoteEventStoreError::DuplicateId(event.id));
+        }
+        NoteEventStoreError::check_range(&event.id, event.start_ticks, event.end_ticks)?;
+
+        self.tree
+            .insert(event.start_ticks, event.end_ticks, event.id.clone());
+        self.events.insert(event.id.clone(), event);
+        Ok(())
+    }
+
+    fn update_event(&mut self, event: NoteEventUpdate) -> Result<(), NoteEventStoreError> {
+        let Some(existing_event) = self.events.get_mut(&event.id) else {
+            return Err(NoteEventStoreError::NotFound(event.id));
+        };
+
+        NoteEventStoreError::check_range(
+            &event.id,
+            event.start_ticks.unwrap_or(existing_event.start_ticks),
+            event.end_ticks.unwrap_or(existing_event.end_ticks),
+        )?;
+
+        if event.start_ticks.is_some() || event.end_ticks.is_some() {
+            self.tree
+                .remove(existing_event.start_ticks, &existing_event.id);
+
+            if let Some(start_ticks) = event.start_ticks {
+                existing_event.start_ticks = start_ticks;
+            }
+            if let Some(end_ticks) = event.end_ticks {
+                existing_event.end_ticks = end_ticks;
+            }
+
+            self.tree.insert(
+                existing_event.start_ticks,
+                existing_event.end_ticks,
+                existing_event.id.clone(),
+            );
+        }
+
+        if let Some(note_number) = event.note_number {
+            existing_event.note_number = note_number;
+        }
+
+        if let Some(velocity) = event.velocity {
+            existing_event.velocity = velocity;
+        }
+
+        if let Some(channel) = event.channel {
+            existing_event.channel = channel;
+        }
+
+        if let Some(track_id) = event.track_id {
+            existing_event.track_id = track_id;
+        }
+
+        Ok(())
+    }
+
+    fn delete_event(&mut self, id: &str) -> Result<NoteEvent, NoteEventStoreError> {
+        let Some(event) = self.events.remove(id) else {
+            return Err(NoteEventStoreError::NotFound(id.to_string()));
+        };
+
+        self.tree.remove(event.start_ticks, id);
+        Ok(event)
+    }
+
+    fn get_event(&self, id: &str) -> Option<&NoteEvent> {
+        self.events.get(id)
+    }
+
+    fn iter_events(&self) -> impl Iterator<Item = &NoteEvent> {
+        self.events.values()
+    }
+
+    fn iter_events_by_range(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.tree
+            .overlapping(start_ticks, end_ticks)
+            .filter_map(|id| self.events.get(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::note_event_store::test_util;
+
+    use super::*;
+
+    #[test]
+    fn test_add_event() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::add_event(&mut store);
+    }
+
+    #[test]
+    fn test_add_events() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::add_events(&mut store);
+    }
+
+    #[test]
+    fn test_add_event_duplicate_id() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::add_event_duplicate_id(&mut store);
+    }
+
+    #[test]
+    fn test_add_event_invalid_range() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::add_event_invalid_range(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_all_fields() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::update_event_all_fields(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_partial() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::update_event_partial(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_all_none() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::update_event_all_none(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_not_found() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::update_event_not_found(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_invalid_range() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::update_event_invalid_range(&mut store);
+    }
+
+    #[test]
+    fn test_update_events() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::update_events(&mut store);
+    }
+
+    #[test]
+    fn test_delete_event() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::delete_event(&mut store);
+    }
+
+    #[test]
+    fn test_delete_event_not_found() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::delete_event_not_found(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::delete_events(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events_on_track() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::delete_events_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_add_events_atomic() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::add_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_update_events_atomic() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::update_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events_atomic() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::delete_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::transaction(&mut store);
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::transaction_rollback(&mut store);
+    }
+
+    #[test]
+    fn test_get_event() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::get_event(&mut store);
+    }
+
+    #[test]
+    fn test_iter_events() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::iter_events(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::get_events_by_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_ordered() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::get_events_by_range_ordered(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::get_events_by_range_with(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_on_track() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::get_events_by_range_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_iter_events_by_range() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::iter_events_by_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_random() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::get_events_by_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::get_events_at(&mut store);
+    }
+
+    #[test]
+    fn test_next_and_prev_event() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::next_and_prev_event(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_pitch() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::get_events_by_pitch(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::aggregates_in_range(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range_random() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::aggregates_in_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_query() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::query(&mut store);
+    }
+
+    #[test]
+    fn test_query_random() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::query_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::get_events_in_region(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_time_range() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::get_events_by_time_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_bars() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::get_events_in_bars(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at_random() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::get_events_at_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with_random() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::get_events_by_range_with_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region_random() {
+        let mut store = IntervalTreeNoteEventStore::new();
+        test_util::get_events_in_region_random(&mut store);
+    }
+}
@@ -1,5 +1,17 @@
 pub mod base;
 pub mod btree;
+pub mod control_event;
+pub mod error;
 pub mod event;
+pub mod history;
+pub mod id;
+pub mod interval_tree;
+pub mod observable;
+pub mod operation;
+pub mod pitch_interval;
+pub mod query;
+pub mod soa;
+pub mod sorted_vec;
 mod test_util;
+pub mod transaction;
 pub mod vec;
@@ -0,0 +1,682 @@
+use super::{
+    base::NoteEventStore,
+    error::NoteEventStoreError,
+    event::{NoteEvent, NoteEventUpdate, VelocityStats},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NoteEventChange {
+    Added(NoteEvent),
+    Updated { old: NoteEvent, new: NoteEvent },
+    Deleted(NoteEvent),
+}
+
+pub type NoteEventListener = Box<dyn FnMut(&NoteEventChange) + Send>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+pub struct ObservableNoteEventStore<S: NoteEventStore> {
+    store: S,
+    listeners: Vec<(SubscriptionId, NoteEventListener)>,
+    next_subscription_id: u64,
+    // Changes made inside a transaction are held back until the outermost one
+    // commits, so listeners never see changes that end up rolled back. Each
+    // mark is where an open transaction's changes start.
+    pending_changes: Vec<NoteEventChange>,
+    transaction_marks: Vec<usize>,
+}
+
+impl<S: NoteEventStore> ObservableNoteEventStore<S> {
+    pub fn new(store: S) -> Self {
+        ObservableNoteEventStore {
+            store,
+            listeners: vec![],
+            next_subscription_id: 0,
+            pending_changes: vec![],
+            transaction_marks: vec![],
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.store
+    }
+
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+
+    pub fn subscribe(
+        &mut self,
+        listener: impl FnMut(&NoteEventChange) + Send + 'static,
+    ) -> SubscriptionId {
+        let id = SubscriptionId(self.next_subscription_id);
+        self.next_subscription_id += 1;
+        self.listeners.push((id, Box::new(listener)));
+        id
+    }
+
+    /// Returns `false` if `id` was not subscribed.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let len = self.listeners.len();
+        self.listeners
+            .retain(|(subscription_id, _)| *subscription_id != id);
+        self.listeners.len() != len
+    }
+
+    fn notify(&mut self, change: NoteEventChange) {
+        if self.transaction_marks.is_empty() {
+            for (_, listener) in &mut self.listeners {
+                listener(&change);
+            }
+        } else {
+            self.pending_changes.push(change);
+        }
+    }
+}
+
+impl<S: NoteEventStore> NoteEventStore for ObservableNoteEventStore<S> {
+    fn add_event(&mut self, event: NoteEvent) -> Result<(), NoteEventStoreError> {
+        let id = event.id.clone();
+        self.store.add_event(event)?;
+        if let Some(event) = self.store.get_event(&id) {
+            self.notify(NoteEventChange::Added(event.clone()));
+        }
+        Ok(())
+    }
+
+    fn update_event(&mut self, event: NoteEventUpdate) -> Result<(), NoteEventStoreError> {
+        let id = event.id.clone();
+        let Some(old) = self.store.get_event(&id).cloned() else {
+            return Err(NoteEventStoreError::NotFound(id));
+        };
+        self.store.update_event(event)?;
+        if let Some(new) = self.store.get_event(&id) {
+            let new = new.clone();
+            self.notify(NoteEventChange::Updated { old, new });
+        }
+        Ok(())
+    }
+
+    fn delete_event(&mut self, id: &str) -> Result<NoteEvent, NoteEventStoreError> {
+        let event = self.store.delete_event(id)?;
+        self.notify(NoteEventChange::Deleted(event.clone()));
+        Ok(event)
+    }
+
+    fn get_event(&self, id: &str) -> Option<&NoteEvent> {
+        self.store.get_event(id)
+    }
+
+    fn begin_transaction(&mut self) {
+        self.transaction_marks.push(self.pending_changes.len());
+        self.store.begin_transaction();
+    }
+
+    fn end_transaction(&mut self, committed: bool) {
+        self.store.end_transaction(committed);
+
+        let mark = self
+            .transaction_marks
+            .pop()
+            .expect("every transaction end has a matching begin");
+        if !committed {
+            self.pending_changes.truncate(mark);
+        }
+        if self.transaction_marks.is_empty() {
+            for change in std::mem::take(&mut self.pending_changes) {
+                self.notify(change);
+            }
+        }
+    }
+
+    fn iter_events(&self) -> impl Iterator<Item = &NoteEvent> {
+        self.store.iter_events()
+    }
+
+    fn iter_events_by_range(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.store.iter_events_by_range(start_ticks, end_ticks)
+    }
+
+    fn iter_events_on_track(&self, track_id: u16) -> impl Iterator<Item = &NoteEvent> {
+        self.store.iter_events_on_track(track_id)
+    }
+
+    fn iter_events_by_range_on_track(
+        &self,
+        track_id: u16,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.store
+            .iter_events_by_range_on_track(track_id, start_ticks, end_ticks)
+    }
+
+    fn count_events_in_range(&self, start_ticks: u64, end_ticks: u64) -> usize {
+        self.store.count_events_in_range(start_ticks, end_ticks)
+    }
+
+    fn max_polyphony_in_range(&self, start_ticks: u64, end_ticks: u64) -> usize {
+        self.store.max_polyphony_in_range(start_ticks, end_ticks)
+    }
+
+    fn pitch_bounds_in_range(&self, start_ticks: u64, end_ticks: u64) -> Option<(u8, u8)> {
+        self.store.pitch_bounds_in_range(start_ticks, end_ticks)
+    }
+
+    fn velocity_stats_in_range(&self, start_ticks: u64, end_ticks: u64) -> Option<VelocityStats> {
+        self.store.velocity_stats_in_range(start_ticks, end_ticks)
+    }
+
+    fn iter_events_at(&self, ticks: u64) -> impl Iterator<Item = &NoteEvent> {
+        self.store.iter_events_at(ticks)
+    }
+
+    fn next_event_after(&self, ticks: u64) -> Option<&NoteEvent> {
+        self.store.next_event_after(ticks)
+    }
+
+    fn prev_event_before(&self, ticks: u64) -> Option<&NoteEvent> {
+        self.store.prev_event_before(ticks)
+    }
+
+    fn next_event_with_pitch(&self, ticks: u64, note_number: u8) -> Option<&NoteEvent> {
+        self.store.next_event_with_pitch(ticks, note_number)
+    }
+
+    fn iter_events_by_pitch_range(
+        &self,
+        low_note: u8,
+        high_note: u8,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.store.iter_events_by_pitch_range(low_note, high_note)
+    }
+
+    fn iter_events_in_region(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+        low_note: u8,
+        high_note: u8,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.store
+            .iter_events_in_region(start_ticks, end_ticks, low_note, high_note)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::note_event_store::{
+        btree::BTreeNoteEventStore, history::HistoryNoteEventStore, test_util,
+    };
+
+    use super::*;
+
+    fn new_store() -> ObservableNoteEventStore<BTreeNoteEventStore> {
+        ObservableNoteEventStore::new(BTreeNoteEventStore::new())
+    }
+
+    fn note_event(id: &str, start_ticks: u64, end_ticks: u64) -> NoteEvent {
+        NoteEvent {
+            id: id.to_string(),
+            start_ticks,
+            end_ticks,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        }
+    }
+
+    fn record_changes<S: NoteEventStore>(
+        store: &mut ObservableNoteEventStore<S>,
+    ) -> Arc<Mutex<Vec<NoteEventChange>>> {
+        let changes = Arc::new(Mutex::new(vec![]));
+        let listener_changes = changes.clone();
+        store.subscribe(move |change| listener_changes.lock().unwrap().push(change.clone()));
+        changes
+    }
+
+    #[test]
+    fn test_add_event() {
+        let mut store = new_store();
+        test_util::add_event(&mut store);
+    }
+
+    #[test]
+    fn test_add_events() {
+        let mut store = new_store();
+        test_util::add_events(&mut store);
+    }
+
+    #[test]
+    fn test_add_event_duplicate_id() {
+        let mut store = new_store();
+        test_util::add_event_duplicate_id(&mut store);
+    }
+
+    #[test]
+    fn test_add_event_invalid_range() {
+        let mut store = new_store();
+        test_util::add_event_invalid_range(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_all_fields() {
+        let mut store = new_store();
+        test_util::update_event_all_fields(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_partial() {
+        let mut store = new_store();
+        test_util::update_event_partial(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_all_none() {
+        let mut store = new_store();
+        test_util::update_event_all_none(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_not_found() {
+        let mut store = new_store();
+        test_util::update_event_not_found(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_invalid_range() {
+        let mut store = new_store();
+        test_util::update_event_invalid_range(&mut store);
+    }
+
+    #[test]
+    fn test_update_events() {
+        let mut store = new_store();
+        test_util::update_events(&mut store);
+    }
+
+    #[test]
+    fn test_delete_event() {
+        let mut store = new_store();
+        test_util::delete_event(&mut store);
+    }
+
+    #[test]
+    fn test_delete_event_not_found() {
+        let mut store = new_store();
+        test_util::delete_event_not_found(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events() {
+        let mut store = new_store();
+        test_util::delete_events(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events_on_track() {
+        let mut store = new_store();
+        test_util::delete_events_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_add_events_atomic() {
+        let mut store = new_store();
+        test_util::add_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_update_events_atomic() {
+        let mut store = new_store();
+        test_util::update_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events_atomic() {
+        let mut store = new_store();
+        test_util::delete_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut store = new_store();
+        test_util::transaction(&mut store);
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        let mut store = new_store();
+        test_util::transaction_rollback(&mut store);
+    }
+
+    #[test]
+    fn test_get_event() {
+        let mut store = new_store();
+        test_util::get_event(&mut store);
+    }
+
+    #[test]
+    fn test_iter_events() {
+        let mut store = new_store();
+        test_util::iter_events(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range() {
+        let mut store = new_store();
+        test_util::get_events_by_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_ordered() {
+        let mut store = new_store();
+        test_util::get_events_by_range_ordered(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with() {
+        let mut store = new_store();
+        test_util::get_events_by_range_with(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_on_track() {
+        let mut store = new_store();
+        test_util::get_events_by_range_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_iter_events_by_range() {
+        let mut store = new_store();
+        test_util::iter_events_by_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_random() {
+        let mut store = new_store();
+        test_util::get_events_by_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at() {
+        let mut store = new_store();
+        test_util::get_events_at(&mut store);
+    }
+
+    #[test]
+    fn test_next_and_prev_event() {
+        let mut store = new_store();
+        test_util::next_and_prev_event(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_pitch() {
+        let mut store = new_store();
+        test_util::get_events_by_pitch(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range() {
+        let mut store = new_store();
+        test_util::aggregates_in_range(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range_random() {
+        let mut store = new_store();
+        test_util::aggregates_in_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_query() {
+        let mut store = new_store();
+        test_util::query(&mut store);
+    }
+
+    #[test]
+    fn test_query_random() {
+        let mut store = new_store();
+        test_util::query_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region() {
+        let mut store = new_store();
+        test_util::get_events_in_region(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_time_range() {
+        let mut store = new_store();
+        test_util::get_events_by_time_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_bars() {
+        let mut store = new_store();
+        test_util::get_events_in_bars(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at_random() {
+        let mut store = new_store();
+        test_util::get_events_at_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with_random() {
+        let mut store = new_store();
+        test_util::get_events_by_range_with_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region_random() {
+        let mut store = new_store();
+        test_util::get_events_in_region_random(&mut store);
+    }
+
+    #[test]
+    fn test_notify_changes() {
+        let mut store = new_store();
+        let changes = record_changes(&mut store);
+
+        store.add_event(note_event("0", 0, 10)).unwrap();
+        store
+            .update_event(NoteEventUpdate {
+                id: "0".to_string(),
+                start_ticks: None,
+                end_ticks: Some(20),
+                note_number: None,
+                velocity: None,
+                channel: None,
+                track_id: None,
+            })
+            .unwrap();
+        store.delete_event("0").unwrap();
+
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                NoteEventChange::Added(note_event("0", 0, 10)),
+                NoteEventChange::Updated {
+                    old: note_event("0", 0, 10),
+                    new: note_event("0", 0, 20),
+                },
+                NoteEventChange::Deleted(note_event("0", 0, 20)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failed_operation_is_not_notified() {
+        let mut store = new_store();
+        store.add_event(note_event("0", 0, 10)).unwrap();
+        let changes = record_changes(&mut store);
+
+        assert!(store.add_event(note_event("0", 0, 10)).is_err());
+        assert!(store.delete_event("1").is_err());
+        assert!(store
+            .update_event(NoteEventUpdate {
+                id: "0".to_string(),
+                start_ticks: Some(20),
+                end_ticks: None,
+                note_number: None,
+                velocity: None,
+                channel: None,
+                track_id: None,
+            })
+            .is_err());
+
+        assert!(changes.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_batch_notifies_each_change() {
+        let mut store = new_store();
+        let changes = record_changes(&mut store);
+
+        store
+            .add_events(vec![note_event("0", 0, 10), note_event("1", 10, 20)])
+            .unwrap();
+
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                NoteEventChange::Added(note_event("0", 0, 10)),
+                NoteEventChange::Added(note_event("1", 10, 20)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rolled_back_transaction_is_not_notified() {
+        let mut store = new_store();
+        store.add_event(note_event("0", 0, 10)).unwrap();
+        let changes = record_changes(&mut store);
+
+        assert!(store
+            .add_events(vec![note_event("1", 0, 10), note_event("0", 0, 10)])
+            .is_err());
+        assert!(store.delete_events(vec!["0", "2"]).is_err());
+
+        assert!(changes.lock().unwrap().is_empty());
+        assert!(store.get_event("0").is_some());
+    }
+
+    #[test]
+    fn test_unsubscribe() {
+        let mut store = new_store();
+        let changes = Arc::new(Mutex::new(vec![]));
+        let listener_changes = changes.clone();
+        let id =
+            store.subscribe(move |change| listener_changes.lock().unwrap().push(change.clone()));
+
+        store.add_event(note_event("0", 0, 10)).unwrap();
+
+        assert!(store.unsubscribe(id));
+        assert!(!store.unsubscribe(id));
+
+        store.add_event(note_event("1", 0, 10)).unwrap();
+
+        assert_eq!(changes.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_notify_undo() {
+        let mut observable = ObservableNoteEventStore::new(BTreeNoteEventStore::new());
+        let changes = record_changes(&mut observable);
+        let mut store = HistoryNoteEventStore::new(observable);
+
+        store.add_event(note_event("0", 0, 10)).unwrap();
+        store.undo().unwrap();
+
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                NoteEventChange::Added(note_event("0", 0, 10)),
+                NoteEventChange::Deleted(note_event("0", 0, 10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failed_batch_over_history() {
+        let mut store =
+            ObservableNoteEventStore::new(HistoryNoteEventStore::new(BTreeNoteEventStore::new()));
+        let changes = record_changes(&mut store);
+
+        store.add_event(note_event("0", 0, 10)).unwrap();
+        store
+            .add_events(vec![note_event("1", 0, 10), note_event("2", 10, 20)])
+            .unwrap();
+        assert!(store
+            .add_events(vec![note_event("3", 0, 10), note_event("0", 0, 10)])
+            .is_err());
+
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                NoteEventChange::Added(note_event("0", 0, 10)),
+                NoteEventChange::Added(note_event("1", 0, 10)),
+                NoteEventChange::Added(note_event("2", 10, 20)),
+            ]
+        );
+
+        // One entry for the single add and one for the batch.
+        let mut history = store.into_inner();
+        history.undo().unwrap();
+
+        assert!(history.get_event("0").is_some());
+        assert!(history.get_event("1").is_none());
+        assert!(history.get_event("2").is_none());
+
+        history.undo().unwrap();
+
+        assert!(history.iter_events().next().is_none());
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_failed_batch_under_history() {
+        let mut observable = ObservableNoteEventStore::new(BTreeNoteEventStore::new());
+        let changes = record_changes(&mut observable);
+        let mut store = HistoryNoteEventStore::new(observable);
+
+        store.add_event(note_event("0", 0, 10)).unwrap();
+        assert!(store
+            .add_events(vec![note_event("1", 0, 10), note_event("0", 0, 10)])
+            .is_err());
+        assert!(store
+            .group(|store| {
+                store.delete_event("0")?;
+                store.delete_event("1")
+            })
+            .is_err());
+
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![NoteEventChange::Added(note_event("0", 0, 10))]
+        );
+
+        store
+            .add_events(vec![note_event("1", 0, 10), note_event("2", 10, 20)])
+            .unwrap();
+        store.undo().unwrap();
+
+        assert!(store.get_event("0").is_some());
+        assert!(store.get_event("1").is_none());
+
+        store.undo().unwrap();
+
+        assert!(store.iter_events().next().is_none());
+        assert!(!store.can_undo());
+    }
+}
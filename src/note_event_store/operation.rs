@@ -0,0 +1,50 @@
+use super::{
+    base::NoteEventStore,
+    error::NoteEventStoreError,
+    event::{NoteEvent, NoteEventUpdate},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NoteEventOperation {
+    Add(NoteEvent),
+    Update(NoteEventUpdate),
+    Delete(String),
+}
+
+impl NoteEventOperation {
+    /// Applies the operation to `store` and returns the operation that
+    /// reverts it.
+    pub fn apply(
+        self,
+        store: &mut impl NoteEventStore,
+    ) -> Result<NoteEventOperation, NoteEventStoreError> {
+        match self {
+            NoteEventOperation::Add(event) => {
+                let id = event.id.clone();
+                store.add_event(event)?;
+                Ok(NoteEventOperation::Delete(id))
+            }
+            NoteEventOperation::Update(update) => {
+                let Some(existing_event) = store.get_event(&update.id) else {
+                    return Err(NoteEventStoreError::NotFound(update.id));
+                };
+                // Only the fields touched by the update are restored.
+                let inverse = NoteEventUpdate {
+                    id: update.id.clone(),
+                    start_ticks: update.start_ticks.map(|_| existing_event.start_ticks),
+                    end_ticks: update.end_ticks.map(|_| existing_event.end_ticks),
+                    note_number: update.note_number.map(|_| existing_event.note_number),
+                    velocity: update.velocity.map(|_| existing_event.velocity),
+                    channel: update.channel.map(|_| existing_event.channel),
+                    track_id: update.track_id.map(|_| existing_event.track_id),
+                };
+                store.update_event(update)?;
+                Ok(NoteEventOperation::Update(inverse))
+            }
+            NoteEventOperation::Delete(id) => {
+                let event = store.delete_event(&id)?;
+                Ok(NoteEventOperation::Add(event))
+            }
+        }
+    }
+}
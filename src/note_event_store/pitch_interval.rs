@@ -0,0 +1,395 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::{
+    base::NoteEventStore,
+    error::NoteEventStoreError,
+    event::{NoteEvent, NoteEventUpdate},
+    interval_tree::IntervalTree,
+};
+
+// Keeps one interval tree per note number, so a region query only visits the
+// pitches it covers.
+#[derive(Default)]
+pub struct PitchIntervalNoteEventStore {
+    events: HashMap<String, NoteEvent>,
+    trees: BTreeMap<u8, IntervalTree>,
+}
+
+impl PitchIntervalNoteEventStore {
+    pub fn new() -> Self {
+        PitchIntervalNoteEventStore {
+            events: HashMap::new(),
+            trees: BTreeMap::new(),
+        }
+    }
+
+    fn insert_into_tree(&mut self, event: &NoteEvent) {
+        self.trees.entry(event.note_number).or_default().insert(
+            event.start_ticks,
+            event.end_ticks,
+            event.id.clone(),
+        );
+    }
+
+    fn remove_from_tree(&mut self, event: &NoteEvent) {
+        if let Some(tree) = self.trees.get_mut(&event.note_number) {
+            tree.remove(event.start_ticks, &event.id);
+            if tree.is_empty() {
+                self.trees.remove(&event.note_number);
+            }
+        }
+    }
+}
+
+impl NoteEventStore for PitchIntervalNoteEventStore {
+    fn add_event(&mut self, event: NoteEvent) -> Result<(), NoteEventStoreError> {
+        if self.events.contains_key(&event.id) {
+            return Err(NoteEventStoreError::DuplicateId(event.id));
+        }
+        NoteEventStoreError::check_range(&event.id, event.start_ticks, event.end_ticks)?;
+
+        self.insert_into_tree(&event);
+        self.events.insert(event.id.clone(), event);
+        Ok(())
+    }
+
+    fn update_event(&mut self, event: NoteEventUpdate) -> Result<(), NoteEventStoreError> {
+        let Some(existing_event) = self.events.get(&event.id) else {
+            return Err(NoteEventStoreError::NotFound(event.id));
+        };
+
+        let updated_event = NoteEvent {
+            id: event.id,
+            start_ticks: event.start_ticks.unwrap_or(existing_event.start_ticks),
+            end_ticks: event.end_ticks.unwrap_or(existing_event.end_ticks),
+            note_number: event.note_number.unwrap_or(existing_event.note_number),
+            velocity: event.velocity.unwrap_or(existing_event.velocity),
+            channel: event.channel.unwrap_or(existing_event.channel),
+            track_id: event.track_id.unwrap_or(existing_event.track_id),
+        };
+        NoteEventStoreError::check_range(
+            &updated_event.id,
+            updated_event.start_ticks,
+            updated_event.end_ticks,
+        )?;
+
+        if updated_event.start_ticks != existing_event.start_ticks
+            || updated_event.end_ticks != existing_event.end_ticks
+            || updated_event.note_number != existing_event.note_number
+        {
+            let existing_event = existing_event.clone();
+            self.remove_from_tree(&existing_event);
+            self.insert_into_tree(&updated_event);
+        }
+
+        self.events.insert(updated_event.id.clone(), updated_event);
+        Ok(())
+    }
+
+    fn delete_event(&mut self, id: &str) -> Result<NoteEvent, NoteEventStoreError> {
+        let Some(event) = self.events.remove(id) else {
+            return Err(NoteEventStoreError::NotFound(id.to_string()));
+        };
+
+        self.remove_from_tree(&event);
+        Ok(event)
+    }
+
+    fn get_event(&self, id: &str) -> Option<&NoteEvent> {
+        self.events.get(id)
+    }
+
+    fn iter_events(&self) -> impl Iterator<Item = &NoteEvent> {
+        self.events.values()
+    }
+
+    fn iter_events_by_range(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.iter_events_in_region(start_ticks, end_ticks, u8::MIN, u8::MAX)
+    }
+
+    fn iter_events_in_region(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+        low_note: u8,
+        high_note: u8,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        // `BTreeMap::range` panics on an inverted range, which matches nothing.
+        self.trees
+            .range(low_note..=high_note.max(low_note))
+            .filter(move |(note_number, _)| **note_number <= high_note)
+            .flat_map(move |(_, tree)| tree.overlapping(start_ticks, end_ticks))
+            .filter_map(|id| self.events.get(id))
+    }
+
+    fn iter_events_by_pitch_range(
+        &self,
+        low_note: u8,
+        high_note: u8,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.iter_events_in_region(u64::MIN, u64::MAX, low_note, high_note)
+    }
+
+    fn pitch_bounds_in_range(&self, start_ticks: u64, end_ticks: u64) -> Option<(u8, u8)> {
+        // The lowest and highest lanes with an event in the range are enough,
+        // so the lanes between them are never visited.
+        let mut lanes = self
+            .trees
+            .iter()
+            .filter(|(_, tree)| tree.overlapping(start_ticks, end_ticks).next().is_some())
+            .map(|(note_number, _)| *note_number);
+        let low_note = lanes.next()?;
+        Some((low_note, lanes.next_back().unwrap_or(low_note)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::note_event_store::test_util;
+
+    use super::*;
+
+    #[test]
+    fn test_add_event() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::add_event(&mut store);
+    }
+
+    #[test]
+    fn test_add_events() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::add_events(&mut store);
+    }
+
+    #[test]
+    fn test_add_event_duplicate_id() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::add_event_duplicate_id(&mut store);
+    }
+
+    #[test]
+    fn test_add_event_invalid_range() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::add_event_invalid_range(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_all_fields() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::update_event_all_fields(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_partial() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::update_event_partial(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_all_none() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::update_event_all_none(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_not_found() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::update_event_not_found(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_invalid_range() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::update_event_invalid_range(&mut store);
+    }
+
+    #[test]
+    fn test_update_events() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::update_events(&mut store);
+    }
+
+    #[test]
+    fn test_delete_event() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::delete_event(&mut store);
+    }
+
+    #[test]
+    fn test_delete_event_not_found() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::delete_event_not_found(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::delete_events(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events_on_track() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::delete_events_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_add_events_atomic() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::add_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_update_events_atomic() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::update_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events_atomic() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::delete_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::transaction(&mut store);
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::transaction_rollback(&mut store);
+    }
+
+    #[test]
+    fn test_get_event() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::get_event(&mut store);
+    }
+
+    #[test]
+    fn test_iter_events() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::iter_events(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::get_events_by_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_ordered() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::get_events_by_range_ordered(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::get_events_by_range_with(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_on_track() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::get_events_by_range_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_iter_events_by_range() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::iter_events_by_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_random() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::get_events_by_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::get_events_at(&mut store);
+    }
+
+    #[test]
+    fn test_next_and_prev_event() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::next_and_prev_event(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_pitch() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::get_events_by_pitch(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::aggregates_in_range(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range_random() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::aggregates_in_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_query() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::query(&mut store);
+    }
+
+    #[test]
+    fn test_query_random() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::query_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::get_events_in_region(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_time_range() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::get_events_by_time_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_bars() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::get_events_in_bars(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at_random() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::get_events_at_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with_random() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::get_events_by_range_with_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region_random() {
+        let mut store = PitchIntervalNoteEventStore::new();
+        test_util::get_events_in_region_random(&mut store);
+    }
+}
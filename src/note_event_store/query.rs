@@ -0,0 +1,174 @@
+use std::ops::{Bound, RangeBounds, RangeInclusive};
+
+use super::{
+    base::NoteEventStore,
+    event::{NoteEvent, NoteEventOrder, RangeSemantics},
+};
+
+/// Collects the constraints of a query before planning it against the
+/// store's indexes. Setting a constraint twice replaces it.
+pub struct NoteEventQuery<'a, S> {
+    store: &'a S,
+    ticks: Option<(u64, u64)>,
+    semantics: RangeSemantics,
+    pitch: Option<RangeInclusive<u8>>,
+    velocity: Option<RangeInclusive<u8>>,
+    channel: Option<u8>,
+    track_id: Option<u16>,
+    order: NoteEventOrder,
+}
+
+impl<'a, S: NoteEventStore> NoteEventQuery<'a, S> {
+    pub(crate) fn new(store: &'a S) -> Self {
+        NoteEventQuery {
+            store,
+            ticks: None,
+            semantics: RangeSemantics::default(),
+            pitch: None,
+            velocity: None,
+            channel: None,
+            track_id: None,
+            order: NoteEventOrder::default(),
+        }
+    }
+
+    /// Events overlapping `start_ticks..=end_ticks`, or as set by `semantics`.
+    pub fn range(mut self, start_ticks: u64, end_ticks: u64) -> Self {
+        self.ticks = Some((start_ticks, end_ticks));
+        self
+    }
+
+    pub fn semantics(mut self, semantics: RangeSemantics) -> Self {
+        self.semantics = semantics;
+        self
+    }
+
+    pub fn pitch(mut self, note_numbers: impl RangeBounds<u8>) -> Self {
+        self.pitch = Some(to_inclusive(note_numbers));
+        self
+    }
+
+    pub fn velocity(mut self, velocities: impl RangeBounds<u8>) -> Self {
+        self.velocity = Some(to_inclusive(velocities));
+        self
+    }
+
+    pub fn channel(mut self, channel: u8) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    pub fn track(mut self, track_id: u16) -> Self {
+        self.track_id = Some(track_id);
+        self
+    }
+
+    /// The order `collect` sorts by.
+    pub fn order(mut self, order: NoteEventOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn matches(&self, event: &NoteEvent) -> bool {
+        self.ticks.is_none_or(|(start_ticks, end_ticks)| {
+            self.semantics.overlaps(event, start_ticks, end_ticks)
+        }) && self
+            .pitch
+            .as_ref()
+            .is_none_or(|pitch| pitch.contains(&event.note_number))
+            && self
+                .velocity
+                .as_ref()
+                .is_none_or(|velocity| velocity.contains(&event.velocity))
+            && self.channel.is_none_or(|channel| event.channel == channel)
+            && self
+                .track_id
+                .is_none_or(|track_id| event.track_id == track_id)
+    }
+
+    /// Yields matching events in no particular order.
+    pub fn iter(self) -> impl Iterator<Item = &'a NoteEvent> {
+        let store = self.store;
+        let is_empty = self.pitch.as_ref().is_some_and(|pitch| pitch.is_empty())
+            || self
+                .velocity
+                .as_ref()
+                .is_some_and(|velocity| velocity.is_empty());
+
+        // Start from the most selective query the store can answer with its
+        // own indexes, then check every constraint on what it yields.
+        let candidates: Box<dyn Iterator<Item = &'a NoteEvent> + 'a> =
+            match (self.ticks, &self.pitch, self.track_id) {
+                _ if is_empty => Box::new(std::iter::empty()),
+                (Some((start_ticks, end_ticks)), Some(pitch), _) => {
+                    Box::new(store.iter_events_in_region(
+                        start_ticks,
+                        end_ticks,
+                        *pitch.start(),
+                        *pitch.end(),
+                    ))
+                }
+                (Some((start_ticks, end_ticks)), None, Some(track_id)) => {
+                    Box::new(store.iter_events_by_range_on_track(track_id, start_ticks, end_ticks))
+                }
+                (Some((start_ticks, end_ticks)), None, None) => {
+                    Box::new(store.iter_events_by_range(start_ticks, end_ticks))
+                }
+                (None, Some(pitch), _) => {
+                    Box::new(store.iter_events_by_pitch_range(*pitch.start(), *pitch.end()))
+                }
+                (None, None, Some(track_id)) => Box::new(store.iter_events_on_track(track_id)),
+                (None, None, None) => Box::new(store.iter_events()),
+            };
+
+        candidates.filter(move |event| self.matches(event))
+    }
+
+    pub fn collect(self) -> Vec<&'a NoteEvent> {
+        let order = self.order;
+        let mut events: Vec<&NoteEvent> = self.iter().collect();
+        events.sort_unstable_by(|a, b| order.compare(a, b));
+        events
+    }
+
+    pub fn count(self) -> usize {
+        self.iter().count()
+    }
+}
+
+// Empty ranges are represented by `1..=0`.
+#[allow(clippy::reversed_empty_ranges)]
+fn to_inclusive(range: impl RangeBounds<u8>) -> RangeInclusive<u8> {
+    let start = match range.start_bound().cloned() {
+        Bound::Included(start) => start,
+        Bound::Excluded(u8::MAX) => return 1..=0,
+        Bound::Excluded(start) => start + 1,
+        Bound::Unbounded => u8::MIN,
+    };
+    let end = match range.end_bound().cloned() {
+        Bound::Included(end) => end,
+        Bound::Excluded(u8::MIN) => return 1..=0,
+        Bound::Excluded(end) => end - 1,
+        Bound::Unbounded => u8::MAX,
+    };
+    start..=end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_inclusive() {
+        assert_eq!(to_inclusive(60..72), 60..=71);
+        assert_eq!(to_inclusive(..40), 0..=39);
+        assert_eq!(to_inclusive(100..), 100..=255);
+        assert_eq!(to_inclusive(..), 0..=255);
+        assert_eq!(to_inclusive(64..=64), 64..=64);
+        assert!(to_inclusive(..0).is_empty());
+        assert!(to_inclusive((Bound::Excluded(255), Bound::Unbounded)).is_empty());
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 72..60;
+        assert!(to_inclusive(reversed).is_empty());
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::HashMap;
+
+use super::{
+    base::{max_polyphony, NoteEventStore},
+    error::NoteEventStoreError,
+    event::{NoteEvent, NoteEventUpdate, VelocityStats},
+};
+
+/// Keeps each field scanned by queries in its own column, so a scan only
+/// touches the ticks and note numbers it compares.
+///
+/// This is not a pure struct of arrays: `NoteEventStore` hands out
+/// `&NoteEvent`, which needs the whole event in memory, so `rows` stays the
+/// source of truth and the columns are copies of the fields queries scan.
+/// Writes pay for the copies; `benches/note_event_store` compares both sides
+/// against the other stores.
+#[derive(Default)]
+pub struct SoaNoteEventStore {
+    rows: Vec<NoteEvent>,
+    start_ticks: Vec<u64>,
+    end_ticks: Vec<u64>,
+    note_numbers: Vec<u8>,
+    velocities: Vec<u8>,
+    track_ids: Vec<u16>,
+    positions: HashMap<String, usize>,
+}
+
+impl SoaNoteEventStore {
+    pub fn new() -> Self {
+        SoaNoteEventStore {
+            rows: vec![],
+            start_ticks: vec![],
+            end_ticks: vec![],
+            note_numbers: vec![],
+            velocities: vec![],
+            track_ids: vec![],
+            positions: HashMap::new(),
+        }
+    }
+
+    pub fn start_ticks(&self) -> &[u64] {
+        &self.start_ticks
+    }
+
+    pub fn end_ticks(&self) -> &[u64] {
+        &self.end_ticks
+    }
+
+    pub fn note_numbers(&self) -> &[u8] {
+        &self.note_numbers
+    }
+
+    pub fn velocities(&self) -> &[u8] {
+        &self.velocities
+    }
+
+    pub fn track_ids(&self) -> &[u16] {
+        &self.track_ids
+    }
+
+    /// Copies the scanned fields of the row at `index` into the columns,
+    /// pushing them for a row that was just pushed.
+    fn write_columns(&mut self, index: usize) {
+        let row = &self.rows[index];
+        if index == self.start_ticks.len() {
+            self.start_ticks.push(row.start_ticks);
+            self.end_ticks.push(row.end_ticks);
+            self.note_numbers.push(row.note_number);
+            self.velocities.push(row.velocity);
+            self.track_ids.push(row.track_id);
+        } else {
+            self.start_ticks[index] = row.start_ticks;
+            self.end_ticks[index] = row.end_ticks;
+            self.note_numbers[index] = row.note_number;
+            self.velocities[index] = row.velocity;
+            self.track_ids[index] = row.track_id;
+        }
+    }
+
+    fn iter_indices_by_range(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = usize> + '_ {
+        self.start_ticks
+            .iter()
+            .zip(&self.end_ticks)
+            .enumerate()
+            .filter(move |(_, (start, end))| **end >= start_ticks && **start <= end_ticks)
+            .map(|(index, _)| index)
+    }
+}
+
+impl NoteEventStore for SoaNoteEventStore {
+    fn add_event(&mut self, event: NoteEvent) -> Result<(), NoteEventStoreError> {
+        if self.positions.contains_key(&event.id) {
+            return Err(NoteEventStoreError::DuplicateId(event.id));
+        }
+        NoteEventStoreError::check_range(&event.id, event.start_ticks, event.end_ticks)?;
+
+        let index = self.rows.len();
+        self.positions.insert(event.id.clone(), index);
+        self.rows.push(event);
+        self.write_columns(index);
+        Ok(())
+    }
+
+    fn update_event(&mut self, event: NoteEventUpdate) -> Result<(), NoteEventStoreError> {
+        let Some(&index) = self.positions.get(&event.id) else {
+            return Err(NoteEventStoreError::NotFound(event.id));
+        };
+
+        NoteEventStoreError::check_range(
+            &event.id,
+            event.start_ticks.unwrap_or(self.start_ticks[index]),
+            event.end_ticks.unwrap_or(self.end_ticks[index]),
+        )?;
+
+        let row = &mut self.rows[index];
+        if let Some(start_ticks) = event.start_ticks {
+            row.start_ticks = start_ticks;
+        }
+        if let Some(end_ticks) = event.end_ticks {
+            row.end_ticks = end_ticks;
+        }
+        if let Some(note_number) = event.note_number {
+            row.note_number = note_number;
+        }
+        if let Some(velocity) = event.velocity {
+            row.velocity = velocity;
+        }
+        if let Some(channel) = event.channel {
+            row.channel = channel;
+        }
+        if let Some(track_id) = event.track_id {
+            row.track_id = track_id;
+        }
+        self.write_columns(index);
+        Ok(())
+    }
+
+    fn delete_event(&mut self, id: &str) -> Result<NoteEvent, NoteEventStoreError> {
+        let Some(index) = self.positions.remove(id) else {
+            return Err(NoteEventStoreError::NotFound(id.to_string()));
+        };
+
+        let event = self.rows.swap_remove(index);
+        self.start_ticks.swap_remove(index);
+        self.end_ticks.swap_remove(index);
+        self.note_numbers.swap_remove(index);
+        self.velocities.swap_remove(index);
+        self.track_ids.swap_remove(index);
+
+        // The last row was moved into the removed one's place.
+        if let Some(moved) = self.rows.get(index) {
+            self.positions.insert(moved.id.clone(), index);
+        }
+        Ok(event)
+    }
+
+    fn get_event(&self, id: &str) -> Option<&NoteEvent> {
+        self.positions.get(id).map(|&index| &self.rows[index])
+    }
+
+    fn iter_events(&self) -> impl Iterator<Item = &NoteEvent> {
+        self.rows.iter()
+    }
+
+    fn iter_events_by_range(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.iter_indices_by_range(start_ticks, end_ticks)
+            .map(|index| &self.rows[index])
+    }
+
+    fn count_events_in_range(&self, start_ticks: u64, end_ticks: u64) -> usize {
+        self.iter_indices_by_range(start_ticks, end_ticks).count()
+    }
+
+    fn max_polyphony_in_range(&self, start_ticks: u64, end_ticks: u64) -> usize {
+        max_polyphony(
+            self.iter_indices_by_range(start_ticks, end_ticks)
+                .map(|index| (self.start_ticks[index], self.end_ticks[index])),
+            start_ticks,
+            end_ticks,
+        )
+    }
+
+    fn pitch_bounds_in_range(&self, start_ticks: u64, end_ticks: u64) -> Option<(u8, u8)> {
+        self.iter_indices_by_range(start_ticks, end_ticks)
+            .map(|index| self.note_numbers[index])
+            .fold(None, |bounds, note| match bounds {
+                None => Some((note, note)),
+                Some((low, high)) => Some((low.min(note), high.max(note))),
+            })
+    }
+
+    fn velocity_stats_in_range(&self, start_ticks: u64, end_ticks: u64) -> Option<VelocityStats> {
+        VelocityStats::from_velocities(
+            self.iter_indices_by_range(start_ticks, end_ticks)
+                .map(|index| self.velocities[index]),
+        )
+    }
+
+    fn iter_events_on_track(&self, track_id: u16) -> impl Iterator<Item = &NoteEvent> {
+        self.track_ids
+            .iter()
+            .enumerate()
+            .filter(move |(_, id)| **id == track_id)
+            .map(|(index, _)| &self.rows[index])
+    }
+
+    fn iter_events_by_range_on_track(
+        &self,
+        track_id: u16,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.iter_indices_by_range(start_ticks, end_ticks)
+            .filter(move |&index| self.track_ids[index] == track_id)
+            .map(|index| &self.rows[index])
+    }
+
+    fn iter_events_at(&self, ticks: u64) -> impl Iterator<Item = &NoteEvent> {
+        self.start_ticks
+            .iter()
+            .zip(&self.end_ticks)
+            .enumerate()
+            .filter(move |(_, (start, end))| **start <= ticks && ticks < **end)
+            .map(|(index, _)| &self.rows[index])
+    }
+
+    fn iter_events_in_region(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+        low_note: u8,
+        high_note: u8,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.iter_indices_by_range(start_ticks, end_ticks)
+            .filter(move |&index| (low_note..=high_note).contains(&self.note_numbers[index]))
+            .map(|index| &self.rows[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::note_event_store::test_util;
+
+    use super::*;
+
+    #[test]
+    fn test_add_event() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::add_event(&mut store);
+    }
+
+    #[test]
+    fn test_add_events() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::add_events(&mut store);
+    }
+
+    #[test]
+    fn test_add_event_duplicate_id() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::add_event_duplicate_id(&mut store);
+    }
+
+    #[test]
+    fn test_add_event_invalid_range() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::add_event_invalid_range(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_all_fields() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::update_event_all_fields(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_partial() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::update_event_partial(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_all_none() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::update_event_all_none(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_not_found() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::update_event_not_found(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_invalid_range() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::update_event_invalid_range(&mut store);
+    }
+
+    #[test]
+    fn test_update_events() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::update_events(&mut store);
+    }
+
+    #[test]
+    fn test_delete_event() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::delete_event(&mut store);
+    }
+
+    #[test]
+    fn test_delete_event_not_found() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::delete_event_not_found(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::delete_events(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events_on_track() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::delete_events_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_add_events_atomic() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::add_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_update_events_atomic() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::update_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events_atomic() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::delete_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::transaction(&mut store);
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::transaction_rollback(&mut store);
+    }
+
+    #[test]
+    fn test_get_event() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::get_event(&mut store);
+    }
+
+    #[test]
+    fn test_iter_events() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::iter_events(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::get_events_by_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_ordered() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::get_events_by_range_ordered(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::get_events_by_range_with(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_on_track() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::get_events_by_range_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_iter_events_by_range() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::iter_events_by_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_random() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::get_events_by_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::get_events_at(&mut store);
+    }
+
+    #[test]
+    fn test_next_and_prev_event() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::next_and_prev_event(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_pitch() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::get_events_by_pitch(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::aggregates_in_range(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range_random() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::aggregates_in_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_query() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::query(&mut store);
+    }
+
+    #[test]
+    fn test_query_random() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::query_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::get_events_in_region(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_time_range() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::get_events_by_time_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_bars() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::get_events_in_bars(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at_random() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::get_events_at_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with_random() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::get_events_by_range_with_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region_random() {
+        let mut store = SoaNoteEventStore::new();
+        test_util::get_events_in_region_random(&mut store);
+    }
+
+    #[test]
+    fn test_columns_follow_rows() {
+        let mut store = SoaNoteEventStore::new();
+        for i in 0..3 {
+            store
+                .add_event(NoteEvent {
+                    id: i.to_string(),
+                    start_ticks: i * 10,
+                    end_ticks: i * 10 + 5,
+                    note_number: 60 + i as u8,
+                    velocity: 100,
+                    channel: 0,
+                    track_id: 0,
+                })
+                .unwrap();
+        }
+        store.delete_event("0").unwrap();
+        store
+            .update_event(NoteEventUpdate {
+                id: "2".to_string(),
+                start_ticks: None,
+                end_ticks: Some(30),
+                note_number: None,
+                velocity: Some(80),
+                channel: None,
+                track_id: None,
+            })
+            .unwrap();
+
+        for (index, row) in store.iter_events().enumerate() {
+            assert_eq!(store.start_ticks()[index], row.start_ticks);
+            assert_eq!(store.end_ticks()[index], row.end_ticks);
+            assert_eq!(store.note_numbers()[index], row.note_number);
+            assert_eq!(store.velocities()[index], row.velocity);
+            assert_eq!(store.track_ids()[index], row.track_id);
+            assert_eq!(store.get_event(&row.id), Some(row));
+        }
+        assert_eq!(store.end_ticks(), &[30, 15]);
+    }
+}
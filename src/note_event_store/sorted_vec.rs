@@ -0,0 +1,516 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use super::{
+    base::NoteEventStore,
+    error::NoteEventStoreError,
+    event::{NoteEvent, NoteEventOrder, NoteEventUpdate},
+};
+
+#[derive(Default)]
+pub struct SortedVecNoteEventStore {
+    // Kept in `NoteEventOrder::StartTicks` order.
+    events: Vec<NoteEvent>,
+    positions: HashMap<String, usize>,
+    // The longest `end_ticks - start_ticks`, which bounds how far before a
+    // range an overlapping event can start.
+    max_duration: u64,
+}
+
+impl SortedVecNoteEventStore {
+    pub fn new() -> Self {
+        SortedVecNoteEventStore {
+            events: vec![],
+            positions: HashMap::new(),
+            max_duration: 0,
+        }
+    }
+
+    fn insert_sorted(&mut self, event: NoteEvent) {
+        // Appending events in order, as when recording, never shifts anything.
+        let index = self
+            .events
+            .partition_point(|e| NoteEventOrder::StartTicks.compare(e, &event) == Ordering::Less);
+
+        self.max_duration = self.max_duration.max(duration(&event));
+        self.positions.insert(event.id.clone(), index);
+        self.events.insert(index, event);
+        self.reindex(index + 1);
+    }
+
+    fn remove_at(&mut self, index: usize) -> NoteEvent {
+        let event = self.events.remove(index);
+        self.positions.remove(&event.id);
+        self.reindex(index);
+
+        if duration(&event) == self.max_duration {
+            self.max_duration = self.events.iter().map(duration).max().unwrap_or(0);
+        }
+        event
+    }
+
+    fn reindex(&mut self, from: usize) {
+        for (index, event) in self.events.iter().enumerate().skip(from) {
+            if let Some(position) = self.positions.get_mut(&event.id) {
+                *position = index;
+            }
+        }
+    }
+}
+
+fn duration(event: &NoteEvent) -> u64 {
+    event.end_ticks - event.start_ticks
+}
+
+fn apply_update(event: &mut NoteEvent, update: &NoteEventUpdate) {
+    if let Some(start_ticks) = update.start_ticks {
+        event.start_ticks = start_ticks;
+    }
+    if let Some(end_ticks) = update.end_ticks {
+        event.end_ticks = end_ticks;
+    }
+    if let Some(note_number) = update.note_number {
+        event.note_number = note_number;
+    }
+    if let Some(velocity) = update.velocity {
+        event.velocity = velocity;
+    }
+    if let Some(channel) = update.channel {
+        event.channel = channel;
+    }
+    if let Some(track_id) = update.track_id {
+        event.track_id = track_id;
+    }
+}
+
+impl NoteEventStore for SortedVecNoteEventStore {
+    fn add_event(&mut self, event: NoteEvent) -> Result<(), NoteEventStoreError> {
+        if self.positions.contains_key(&event.id) {
+            return Err(NoteEventStoreError::DuplicateId(event.id));
+        }
+        NoteEventStoreError::check_range(&event.id, event.start_ticks, event.end_ticks)?;
+
+        self.insert_sorted(event);
+        Ok(())
+    }
+
+    fn update_event(&mut self, event: NoteEventUpdate) -> Result<(), NoteEventStoreError> {
+        let Some(&index) = self.positions.get(&event.id) else {
+            return Err(NoteEventStoreError::NotFound(event.id));
+        };
+        let existing_event = &self.events[index];
+
+        NoteEventStoreError::check_range(
+            &event.id,
+            event.start_ticks.unwrap_or(existing_event.start_ticks),
+            event.end_ticks.unwrap_or(existing_event.end_ticks),
+        )?;
+
+        // Only a change to the sort key moves the event; anything else is
+        // written in place.
+        let moves = event
+            .start_ticks
+            .is_some_and(|start_ticks| start_ticks != existing_event.start_ticks)
+            || event
+                .note_number
+                .is_some_and(|note_number| note_number != existing_event.note_number);
+        if moves {
+            let mut updated_event = self.remove_at(index);
+            apply_update(&mut updated_event, &event);
+            self.insert_sorted(updated_event);
+            return Ok(());
+        }
+
+        let previous_duration = duration(existing_event);
+        let updated_event = &mut self.events[index];
+        apply_update(updated_event, &event);
+        let updated_duration = duration(updated_event);
+
+        if updated_duration > self.max_duration {
+            self.max_duration = updated_duration;
+        } else if previous_duration == self.max_duration && updated_duration < previous_duration {
+            self.max_duration = self.events.iter().map(duration).max().unwrap_or(0);
+        }
+        Ok(())
+    }
+
+    fn delete_event(&mut self, id: &str) -> Result<NoteEvent, NoteEventStoreError> {
+        match self.positions.get(id) {
+            Some(&index) => Ok(self.remove_at(index)),
+            None => Err(NoteEventStoreError::NotFound(id.to_string())),
+        }
+    }
+
+    fn get_event(&self, id: &str) -> Option<&NoteEvent> {
+        self.positions.get(id).map(|&index| &self.events[index])
+    }
+
+    fn iter_events(&self) -> impl Iterator<Item = &NoteEvent> {
+        self.events.iter()
+    }
+
+    fn iter_events_by_range(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        let lower = self
+            .events
+            .partition_point(|e| e.start_ticks < start_ticks.saturating_sub(self.max_duration));
+        let upper = self.events.partition_point(|e| e.start_ticks <= end_ticks);
+
+        // An inverted range can put `upper` before `lower`.
+        self.events[lower..upper.max(lower)]
+            .iter()
+            .filter(move |e| e.end_ticks >= start_ticks)
+    }
+
+    fn next_event_after(&self, ticks: u64) -> Option<&NoteEvent> {
+        let index = self.events.partition_point(|e| e.start_ticks <= ticks);
+        self.events.get(index)
+    }
+
+    fn prev_event_before(&self, ticks: u64) -> Option<&NoteEvent> {
+        let index = self.events.partition_point(|e| e.start_ticks < ticks);
+        self.events.get(index.checked_sub(1)?)
+    }
+
+    fn next_event_with_pitch(&self, ticks: u64, note_number: u8) -> Option<&NoteEvent> {
+        let index = self.events.partition_point(|e| e.start_ticks <= ticks);
+        self.events[index..]
+            .iter()
+            .find(|e| e.note_number == note_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::note_event_store::test_util;
+
+    use super::*;
+
+    #[test]
+    fn test_add_event() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::add_event(&mut store);
+    }
+
+    #[test]
+    fn test_add_events() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::add_events(&mut store);
+    }
+
+    #[test]
+    fn test_add_event_duplicate_id() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::add_event_duplicate_id(&mut store);
+    }
+
+    #[test]
+    fn test_add_event_invalid_range() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::add_event_invalid_range(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_all_fields() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::update_event_all_fields(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_partial() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::update_event_partial(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_all_none() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::update_event_all_none(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_not_found() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::update_event_not_found(&mut store);
+    }
+
+    #[test]
+    fn test_update_event_invalid_range() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::update_event_invalid_range(&mut store);
+    }
+
+    #[test]
+    fn test_update_events() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::update_events(&mut store);
+    }
+
+    #[test]
+    fn test_delete_event() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::delete_event(&mut store);
+    }
+
+    #[test]
+    fn test_delete_event_not_found() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::delete_event_not_found(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::delete_events(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events_on_track() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::delete_events_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_add_events_atomic() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::add_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_update_events_atomic() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::update_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events_atomic() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::delete_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::transaction(&mut store);
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::transaction_rollback(&mut store);
+    }
+
+    #[test]
+    fn test_get_event() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::get_event(&mut store);
+    }
+
+    #[test]
+    fn test_iter_events() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::iter_events(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::get_events_by_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_ordered() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::get_events_by_range_ordered(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::get_events_by_range_with(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_on_track() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::get_events_by_range_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_iter_events_by_range() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::iter_events_by_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_random() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::get_events_by_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::get_events_at(&mut store);
+    }
+
+    #[test]
+    fn test_next_and_prev_event() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::next_and_prev_event(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_pitch() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::get_events_by_pitch(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::aggregates_in_range(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range_random() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::aggregates_in_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_query() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::query(&mut store);
+    }
+
+    #[test]
+    fn test_query_random() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::query_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::get_events_in_region(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_time_range() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::get_events_by_time_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_bars() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::get_events_in_bars(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at_random() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::get_events_at_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with_random() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::get_events_by_range_with_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region_random() {
+        let mut store = SortedVecNoteEventStore::new();
+        test_util::get_events_in_region_random(&mut store);
+    }
+
+    #[test]
+    fn test_keeps_events_sorted() {
+        let mut store = SortedVecNoteEventStore::new();
+        for (id, start_ticks, end_ticks) in [("0", 20, 1000), ("1", 0, 10), ("2", 10, 15)] {
+            store
+                .add_event(NoteEvent {
+                    id: id.to_string(),
+                    start_ticks,
+                    end_ticks,
+                    note_number: 60,
+                    velocity: 100,
+                    channel: 0,
+                    track_id: 0,
+                })
+                .unwrap();
+        }
+        store
+            .update_event(NoteEventUpdate {
+                id: "1".to_string(),
+                start_ticks: Some(30),
+                end_ticks: Some(40),
+                note_number: None,
+                velocity: None,
+                channel: None,
+                track_id: None,
+            })
+            .unwrap();
+
+        let ids: Vec<&str> = store.iter_events().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["2", "0", "1"]);
+        for id in ["0", "1", "2"] {
+            assert_eq!(store.get_event(id).unwrap().id, id);
+        }
+        assert_eq!(store.max_duration, 980);
+
+        store.delete_event("0").unwrap();
+
+        assert_eq!(store.max_duration, 10);
+        assert_eq!(store.get_event("1").unwrap().start_ticks, 30);
+    }
+
+    #[test]
+    fn test_update_event_in_place() {
+        let mut store = SortedVecNoteEventStore::new();
+        for (id, start_ticks, end_ticks) in [("0", 0, 100), ("1", 10, 20), ("2", 30, 40)] {
+            store
+                .add_event(NoteEvent {
+                    id: id.to_string(),
+                    start_ticks,
+                    end_ticks,
+                    note_number: 60,
+                    velocity: 100,
+                    channel: 0,
+                    track_id: 0,
+                })
+                .unwrap();
+        }
+        // Same start ticks and note number, so nothing moves.
+        let update = |id: &str, start_ticks: u64, end_ticks: u64| NoteEventUpdate {
+            id: id.to_string(),
+            start_ticks: Some(start_ticks),
+            end_ticks: Some(end_ticks),
+            note_number: Some(60),
+            velocity: Some(64),
+            channel: None,
+            track_id: None,
+        };
+
+        // Shortening the longest event shrinks the bound on overlaps.
+        store.update_event(update("0", 0, 5)).unwrap();
+        assert_eq!(store.max_duration, 10);
+        assert_eq!(store.positions["0"], 0);
+
+        // Lengthening any event grows it.
+        store.update_event(update("2", 30, 500)).unwrap();
+        assert_eq!(store.max_duration, 470);
+        assert_eq!(store.positions["2"], 2);
+        assert_eq!(store.get_event("2").unwrap().velocity, 64);
+
+        let ids: Vec<&str> = store
+            .iter_events_by_range(400, 400)
+            .map(|e| e.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["2"]);
+    }
+}
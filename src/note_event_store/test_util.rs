@@ -1,16 +1,83 @@
-use crate::note_event_store::event::NoteEventUpdate;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use super::{base::NoteEventStore, event::NoteEvent};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    note_event_store::event::{NoteEventOrder, NoteEventUpdate, RangeSemantics, VelocityStats},
+    timing::{
+        meter_map::{MeterChange, MeterMap, TimeSignature},
+        tempo_map::{TempoChange, TempoCurve, TempoMap},
+    },
+};
+
+use super::{
+    base::NoteEventStore, error::NoteEventStoreError, event::NoteEvent, vec::VecNoteEventStore,
+};
+
+/// Refuses to delete the event with `failing_id`, like a store whose
+/// operations can fail for reasons of its own.
+#[allow(dead_code)]
+pub struct FailingDeleteNoteEventStore {
+    store: VecNoteEventStore,
+    failing_id: Rc<RefCell<Option<String>>>,
+}
+
+#[allow(dead_code)]
+impl FailingDeleteNoteEventStore {
+    pub fn new(failing_id: Rc<RefCell<Option<String>>>) -> Self {
+        FailingDeleteNoteEventStore {
+            store: VecNoteEventStore::new(),
+            failing_id,
+        }
+    }
+}
+
+impl NoteEventStore for FailingDeleteNoteEventStore {
+    fn add_event(&mut self, event: NoteEvent) -> Result<(), NoteEventStoreError> {
+        self.store.add_event(event)
+    }
+
+    fn update_event(&mut self, event: NoteEventUpdate) -> Result<(), NoteEventStoreError> {
+        self.store.update_event(event)
+    }
+
+    fn delete_event(&mut self, id: &str) -> Result<NoteEvent, NoteEventStoreError> {
+        if self.failing_id.borrow().as_deref() == Some(id) {
+            return Err(NoteEventStoreError::NotFound(id.to_string()));
+        }
+        self.store.delete_event(id)
+    }
+
+    fn get_event(&self, id: &str) -> Option<&NoteEvent> {
+        self.store.get_event(id)
+    }
+
+    fn iter_events(&self) -> impl Iterator<Item = &NoteEvent> {
+        self.store.iter_events()
+    }
+
+    fn iter_events_by_range(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.store.iter_events_by_range(start_ticks, end_ticks)
+    }
+}
 
 #[allow(dead_code)]
 pub fn add_event(store: &mut impl NoteEventStore) {
-    store.add_event(NoteEvent {
-        id: "0".to_string(),
-        start_ticks: 0,
-        end_ticks: 10,
-        note_number: 60,
-        velocity: 100,
-    });
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
 
     let event = store.get_event("0").unwrap();
 
@@ -23,22 +90,28 @@ pub fn add_event(store: &mut impl NoteEventStore) {
 
 #[allow(dead_code)]
 pub fn add_events(store: &mut impl NoteEventStore) {
-    store.add_events(vec![
-        NoteEvent {
-            id: "0".to_string(),
-            start_ticks: 0,
-            end_ticks: 10,
-            note_number: 60,
-            velocity: 100,
-        },
-        NoteEvent {
-            id: "1".to_string(),
-            start_ticks: 10,
-            end_ticks: 20,
-            note_number: 70,
-            velocity: 90,
-        },
-    ]);
+    store
+        .add_events(vec![
+            NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 10,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "1".to_string(),
+                start_ticks: 10,
+                end_ticks: 20,
+                note_number: 70,
+                velocity: 90,
+                channel: 0,
+                track_id: 0,
+            },
+        ])
+        .unwrap();
 
     let event_0 = store.get_event("0").unwrap();
     let event_1 = store.get_event("1").unwrap();
@@ -57,23 +130,90 @@ pub fn add_events(store: &mut impl NoteEventStore) {
 }
 
 #[allow(dead_code)]
-pub fn update_event_all_fields(store: &mut impl NoteEventStore) {
-    store.add_event(NoteEvent {
+pub fn add_event_duplicate_id(store: &mut impl NoteEventStore) {
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
+
+    let result = store.add_event(NoteEvent {
         id: "0".to_string(),
-        start_ticks: 0,
-        end_ticks: 10,
-        note_number: 60,
-        velocity: 100,
+        start_ticks: 20,
+        end_ticks: 30,
+        note_number: 70,
+        velocity: 90,
+        channel: 0,
+        track_id: 0,
     });
 
-    store.update_event(NoteEventUpdate {
+    assert_eq!(
+        result,
+        Err(NoteEventStoreError::DuplicateId("0".to_string()))
+    );
+
+    let event = store.get_event("0").unwrap();
+
+    assert_eq!(event.start_ticks, 0);
+    assert_eq!(event.end_ticks, 10);
+    assert!(store.get_events_by_range(20, 30).is_empty());
+}
+
+#[allow(dead_code)]
+pub fn add_event_invalid_range(store: &mut impl NoteEventStore) {
+    let result = store.add_event(NoteEvent {
         id: "0".to_string(),
-        start_ticks: Some(5),
-        end_ticks: Some(15),
-        note_number: Some(70),
-        velocity: Some(90),
+        start_ticks: 10,
+        end_ticks: 0,
+        note_number: 60,
+        velocity: 100,
+        channel: 0,
+        track_id: 0,
     });
 
+    assert_eq!(
+        result,
+        Err(NoteEventStoreError::InvalidRange {
+            id: "0".to_string(),
+            start_ticks: 10,
+            end_ticks: 0,
+        })
+    );
+    assert!(store.get_event("0").is_none());
+}
+
+#[allow(dead_code)]
+pub fn update_event_all_fields(store: &mut impl NoteEventStore) {
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
+
+    store
+        .update_event(NoteEventUpdate {
+            id: "0".to_string(),
+            start_ticks: Some(5),
+            end_ticks: Some(15),
+            note_number: Some(70),
+            velocity: Some(90),
+            channel: Some(9),
+            track_id: Some(2),
+        })
+        .unwrap();
+
     let event = store.get_event("0").unwrap();
 
     assert_eq!(event.id, "0");
@@ -81,25 +221,35 @@ pub fn update_event_all_fields(store: &mut impl NoteEventStore) {
     assert_eq!(event.end_ticks, 15);
     assert_eq!(event.note_number, 70);
     assert_eq!(event.velocity, 90);
+    assert_eq!(event.channel, 9);
+    assert_eq!(event.track_id, 2);
 }
 
 #[allow(dead_code)]
 pub fn update_event_partial(store: &mut impl NoteEventStore) {
-    store.add_event(NoteEvent {
-        id: "0".to_string(),
-        start_ticks: 0,
-        end_ticks: 10,
-        note_number: 60,
-        velocity: 100,
-    });
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
 
-    store.update_event(NoteEventUpdate {
-        id: "0".to_string(),
-        start_ticks: Some(5),
-        end_ticks: None,
-        note_number: None,
-        velocity: Some(90),
-    });
+    store
+        .update_event(NoteEventUpdate {
+            id: "0".to_string(),
+            start_ticks: Some(5),
+            end_ticks: None,
+            note_number: None,
+            velocity: Some(90),
+            channel: None,
+            track_id: None,
+        })
+        .unwrap();
 
     let event = store.get_event("0").unwrap();
 
@@ -112,21 +262,29 @@ pub fn update_event_partial(store: &mut impl NoteEventStore) {
 
 #[allow(dead_code)]
 pub fn update_event_all_none(store: &mut impl NoteEventStore) {
-    store.add_event(NoteEvent {
-        id: "0".to_string(),
-        start_ticks: 0,
-        end_ticks: 10,
-        note_number: 60,
-        velocity: 100,
-    });
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
 
-    store.update_event(NoteEventUpdate {
-        id: "0".to_string(),
-        start_ticks: None,
-        end_ticks: None,
-        note_number: None,
-        velocity: None,
-    });
+    store
+        .update_event(NoteEventUpdate {
+            id: "0".to_string(),
+            start_ticks: None,
+            end_ticks: None,
+            note_number: None,
+            velocity: None,
+            channel: None,
+            track_id: None,
+        })
+        .unwrap();
 
     let event = store.get_event("0").unwrap();
 
@@ -139,50 +297,107 @@ pub fn update_event_all_none(store: &mut impl NoteEventStore) {
 
 #[allow(dead_code)]
 pub fn update_event_not_found(store: &mut impl NoteEventStore) {
-    store.update_event(NoteEventUpdate {
+    let result = store.update_event(NoteEventUpdate {
         id: "0".to_string(),
         start_ticks: Some(5),
         end_ticks: Some(15),
         note_number: Some(70),
         velocity: Some(90),
+        channel: None,
+        track_id: None,
     });
 
+    assert_eq!(result, Err(NoteEventStoreError::NotFound("0".to_string())));
     assert!(store.get_event("0").is_none());
 }
 
 #[allow(dead_code)]
-pub fn update_events(store: &mut impl NoteEventStore) {
-    store.add_event(NoteEvent {
+pub fn update_event_invalid_range(store: &mut impl NoteEventStore) {
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
+
+    let result = store.update_event(NoteEventUpdate {
         id: "0".to_string(),
-        start_ticks: 0,
-        end_ticks: 10,
-        note_number: 60,
-        velocity: 100,
-    });
-    store.add_event(NoteEvent {
-        id: "1".to_string(),
-        start_ticks: 10,
-        end_ticks: 20,
-        note_number: 70,
-        velocity: 90,
+        start_ticks: Some(15),
+        end_ticks: None,
+        note_number: Some(70),
+        velocity: None,
+        channel: None,
+        track_id: None,
     });
 
-    store.update_events(vec![
-        NoteEventUpdate {
+    assert_eq!(
+        result,
+        Err(NoteEventStoreError::InvalidRange {
             id: "0".to_string(),
-            start_ticks: Some(5),
-            end_ticks: Some(15),
-            note_number: Some(70),
-            velocity: Some(90),
-        },
-        NoteEventUpdate {
+            start_ticks: 15,
+            end_ticks: 10,
+        })
+    );
+
+    let event = store.get_event("0").unwrap();
+
+    assert_eq!(event.start_ticks, 0);
+    assert_eq!(event.end_ticks, 10);
+    assert_eq!(event.note_number, 60);
+}
+
+#[allow(dead_code)]
+pub fn update_events(store: &mut impl NoteEventStore) {
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
+    store
+        .add_event(NoteEvent {
             id: "1".to_string(),
-            start_ticks: Some(15),
-            end_ticks: Some(25),
-            note_number: Some(80),
-            velocity: Some(80),
-        },
-    ]);
+            start_ticks: 10,
+            end_ticks: 20,
+            note_number: 70,
+            velocity: 90,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
+
+    store
+        .update_events(vec![
+            NoteEventUpdate {
+                id: "0".to_string(),
+                start_ticks: Some(5),
+                end_ticks: Some(15),
+                note_number: Some(70),
+                velocity: Some(90),
+                channel: None,
+                track_id: None,
+            },
+            NoteEventUpdate {
+                id: "1".to_string(),
+                start_ticks: Some(15),
+                end_ticks: Some(25),
+                note_number: Some(80),
+                velocity: Some(80),
+                channel: None,
+                track_id: None,
+            },
+        ])
+        .unwrap();
 
     let event_0 = store.get_event("0").unwrap();
     let event_1 = store.get_event("1").unwrap();
@@ -202,59 +417,355 @@ pub fn update_events(store: &mut impl NoteEventStore) {
 
 #[allow(dead_code)]
 pub fn delete_event(store: &mut impl NoteEventStore) {
-    store.add_event(NoteEvent {
-        id: "0".to_string(),
-        start_ticks: 0,
-        end_ticks: 10,
-        note_number: 60,
-        velocity: 100,
-    });
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
 
-    store.delete_event("0");
+    store.delete_event("0").unwrap();
 
     assert!(store.get_event("0").is_none());
 }
 
 #[allow(dead_code)]
 pub fn delete_event_not_found(store: &mut impl NoteEventStore) {
-    store.delete_event("0");
+    let result = store.delete_event("0");
+
+    assert_eq!(result, Err(NoteEventStoreError::NotFound("0".to_string())));
 
     assert!(store.get_event("0").is_none());
 }
 
 #[allow(dead_code)]
 pub fn delete_events(store: &mut impl NoteEventStore) {
-    store.add_event(NoteEvent {
-        id: "0".to_string(),
-        start_ticks: 0,
-        end_ticks: 10,
-        note_number: 60,
-        velocity: 100,
-    });
-    store.add_event(NoteEvent {
-        id: "1".to_string(),
-        start_ticks: 10,
-        end_ticks: 20,
-        note_number: 70,
-        velocity: 90,
-    });
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
+    store
+        .add_event(NoteEvent {
+            id: "1".to_string(),
+            start_ticks: 10,
+            end_ticks: 20,
+            note_number: 70,
+            velocity: 90,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
 
-    store.delete_events(vec!["0", "1"]);
+    store.delete_events(vec!["0", "1"]).unwrap();
 
     assert!(store.get_event("0").is_none());
     assert!(store.get_event("1").is_none());
 }
 
 #[allow(dead_code)]
-pub fn get_event(store: &mut impl NoteEventStore) {
-    store.add_event(NoteEvent {
-        id: "0".to_string(),
-        start_ticks: 0,
-        end_ticks: 10,
-        note_number: 60,
-        velocity: 100,
+pub fn delete_events_on_track(store: &mut impl NoteEventStore) {
+    store
+        .add_events(
+            (0..6)
+                .map(|i| NoteEvent {
+                    id: i.to_string(),
+                    start_ticks: 10 - i,
+                    end_ticks: 20,
+                    note_number: 60,
+                    velocity: 100,
+                    channel: 0,
+                    track_id: (i % 3) as u16,
+                })
+                .collect(),
+        )
+        .unwrap();
+
+    let ids: Vec<String> = store
+        .delete_events_on_track(1)
+        .unwrap()
+        .into_iter()
+        .map(|e| e.id)
+        .collect();
+
+    assert_eq!(ids, vec!["4", "1"]);
+    assert_eq!(store.iter_events().count(), 4);
+    assert_eq!(store.iter_events_on_track(1).count(), 0);
+    assert!(store.delete_events_on_track(1).unwrap().is_empty());
+
+    store
+        .update_event(NoteEventUpdate {
+            id: "0".to_string(),
+            start_ticks: None,
+            end_ticks: None,
+            note_number: None,
+            velocity: None,
+            channel: None,
+            track_id: Some(2),
+        })
+        .unwrap();
+
+    let mut ids: Vec<&str> = store
+        .iter_events_on_track(2)
+        .map(|e| e.id.as_str())
+        .collect();
+    ids.sort_unstable();
+
+    assert_eq!(ids, vec!["0", "2", "5"]);
+    assert_eq!(
+        store
+            .iter_events_on_track(0)
+            .map(|e| &e.id)
+            .collect::<Vec<_>>(),
+        vec!["3"]
+    );
+}
+
+#[allow(dead_code)]
+pub fn add_events_atomic(store: &mut impl NoteEventStore) {
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
+
+    let result = store.add_events(vec![
+        NoteEvent {
+            id: "1".to_string(),
+            start_ticks: 10,
+            end_ticks: 20,
+            note_number: 70,
+            velocity: 90,
+            channel: 0,
+            track_id: 0,
+        },
+        NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 20,
+            end_ticks: 30,
+            note_number: 80,
+            velocity: 80,
+            channel: 0,
+            track_id: 0,
+        },
+    ]);
+
+    assert_eq!(
+        result,
+        Err(NoteEventStoreError::DuplicateId("0".to_string()))
+    );
+    assert!(store.get_event("1").is_none());
+    assert_eq!(store.get_event("0").unwrap().start_ticks, 0);
+    assert_eq!(store.get_events_by_range(0, 100).len(), 1);
+}
+
+#[allow(dead_code)]
+pub fn update_events_atomic(store: &mut impl NoteEventStore) {
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
+
+    let result = store.update_events(vec![
+        NoteEventUpdate {
+            id: "0".to_string(),
+            start_ticks: Some(5),
+            end_ticks: Some(15),
+            note_number: Some(70),
+            velocity: Some(90),
+            channel: None,
+            track_id: None,
+        },
+        NoteEventUpdate {
+            id: "0".to_string(),
+            start_ticks: Some(20),
+            end_ticks: None,
+            note_number: None,
+            velocity: None,
+            channel: None,
+            track_id: None,
+        },
+    ]);
+
+    assert_eq!(
+        result,
+        Err(NoteEventStoreError::InvalidRange {
+            id: "0".to_string(),
+            start_ticks: 20,
+            end_ticks: 15,
+        })
+    );
+
+    let event = store.get_event("0").unwrap();
+
+    assert_eq!(event.start_ticks, 0);
+    assert_eq!(event.end_ticks, 10);
+    assert_eq!(event.note_number, 60);
+    assert_eq!(event.velocity, 100);
+    assert_eq!(store.get_events_by_range(0, 4).len(), 1);
+}
+
+#[allow(dead_code)]
+pub fn delete_events_atomic(store: &mut impl NoteEventStore) {
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
+    store
+        .add_event(NoteEvent {
+            id: "1".to_string(),
+            start_ticks: 10,
+            end_ticks: 20,
+            note_number: 70,
+            velocity: 90,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
+
+    let result = store.delete_events(vec!["0", "1", "0"]);
+
+    assert_eq!(result, Err(NoteEventStoreError::NotFound("0".to_string())));
+    assert!(store.get_event("0").is_some());
+    assert!(store.get_event("1").is_some());
+    assert_eq!(store.get_events_by_range(0, 20).len(), 2);
+}
+
+#[allow(dead_code)]
+pub fn transaction(store: &mut impl NoteEventStore) {
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
+
+    let deleted = store
+        .transaction(|transaction| {
+            transaction.add_event(NoteEvent {
+                id: "1".to_string(),
+                start_ticks: 10,
+                end_ticks: 20,
+                note_number: 70,
+                velocity: 90,
+                channel: 0,
+                track_id: 0,
+            })?;
+            transaction.update_event(NoteEventUpdate {
+                id: "1".to_string(),
+                start_ticks: None,
+                end_ticks: Some(30),
+                note_number: None,
+                velocity: None,
+                channel: None,
+                track_id: None,
+            })?;
+            assert_eq!(transaction.get_event("1").unwrap().end_ticks, 30);
+            transaction.delete_event("0")
+        })
+        .unwrap();
+
+    assert_eq!(deleted.id, "0");
+    assert!(store.get_event("0").is_none());
+    assert_eq!(store.get_event("1").unwrap().end_ticks, 30);
+}
+
+#[allow(dead_code)]
+pub fn transaction_rollback(store: &mut impl NoteEventStore) {
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
+
+    let result: Result<(), NoteEventStoreError> = store.transaction(|transaction| {
+        transaction.delete_event("0")?;
+        transaction.add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 100,
+            end_ticks: 200,
+            note_number: 70,
+            velocity: 90,
+            channel: 0,
+            track_id: 0,
+        })?;
+        transaction.update_event(NoteEventUpdate {
+            id: "1".to_string(),
+            start_ticks: Some(0),
+            end_ticks: None,
+            note_number: None,
+            velocity: None,
+            channel: None,
+            track_id: None,
+        })
     });
 
+    assert_eq!(result, Err(NoteEventStoreError::NotFound("1".to_string())));
+
+    let event = store.get_event("0").unwrap();
+
+    assert_eq!(event.start_ticks, 0);
+    assert_eq!(event.end_ticks, 10);
+    assert!(store.get_events_by_range(100, 200).is_empty());
+    assert_eq!(store.get_events_by_range(0, 10).len(), 1);
+}
+
+#[allow(dead_code)]
+pub fn get_event(store: &mut impl NoteEventStore) {
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
+
     let event = store.get_event("0").unwrap();
 
     assert_eq!(event.id, "0");
@@ -264,62 +775,1187 @@ pub fn get_event(store: &mut impl NoteEventStore) {
     assert_eq!(event.velocity, 100);
 }
 
+#[allow(dead_code)]
+pub fn iter_events(store: &mut impl NoteEventStore) {
+    assert!(store.iter_events().next().is_none());
+
+    store
+        .add_events(vec![
+            NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 10,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "1".to_string(),
+                start_ticks: 100,
+                end_ticks: 200,
+                note_number: 70,
+                velocity: 90,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "2".to_string(),
+                start_ticks: 50,
+                end_ticks: 60,
+                note_number: 80,
+                velocity: 80,
+                channel: 0,
+                track_id: 0,
+            },
+        ])
+        .unwrap();
+    store.delete_event("1").unwrap();
+
+    let mut ids: Vec<&str> = store.iter_events().map(|e| e.id.as_str()).collect();
+    ids.sort();
+
+    assert_eq!(ids, vec!["0", "2"]);
+}
+
 #[allow(dead_code)]
 pub fn get_events_by_range(store: &mut impl NoteEventStore) {
-    store.add_event(NoteEvent {
-        id: "0".to_string(),
-        start_ticks: 0,
-        end_ticks: 4,
-        note_number: 60,
-        velocity: 100,
-    });
+    store
+        .add_event(NoteEvent {
+            id: "0".to_string(),
+            start_ticks: 0,
+            end_ticks: 4,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
 
-    store.add_event(NoteEvent {
-        id: "1".to_string(),
-        start_ticks: 0,
-        end_ticks: 5,
-        note_number: 60,
-        velocity: 100,
-    });
+    store
+        .add_event(NoteEvent {
+            id: "1".to_string(),
+            start_ticks: 0,
+            end_ticks: 5,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
 
-    store.add_event(NoteEvent {
-        id: "2".to_string(),
-        start_ticks: 10,
-        end_ticks: 15,
-        note_number: 60,
-        velocity: 100,
-    });
+    store
+        .add_event(NoteEvent {
+            id: "2".to_string(),
+            start_ticks: 10,
+            end_ticks: 15,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
 
-    store.add_event(NoteEvent {
-        id: "3".to_string(),
-        start_ticks: 11,
-        end_ticks: 15,
-        note_number: 60,
-        velocity: 100,
-    });
+    store
+        .add_event(NoteEvent {
+            id: "3".to_string(),
+            start_ticks: 11,
+            end_ticks: 15,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
 
-    store.add_event(NoteEvent {
-        id: "4".to_string(),
-        start_ticks: 0,
-        end_ticks: 15,
-        note_number: 60,
-        velocity: 100,
-    });
+    store
+        .add_event(NoteEvent {
+            id: "4".to_string(),
+            start_ticks: 0,
+            end_ticks: 15,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
 
-    store.add_event(NoteEvent {
-        id: "5".to_string(),
-        start_ticks: 7,
-        end_ticks: 8,
-        note_number: 60,
-        velocity: 100,
-    });
+    store
+        .add_event(NoteEvent {
+            id: "5".to_string(),
+            start_ticks: 7,
+            end_ticks: 8,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        })
+        .unwrap();
 
-    let mut events = store.get_events_by_range(5, 10);
-    events.sort_by(|a, b| a.id.cmp(&b.id));
+    let events = store.get_events_by_range(5, 10);
 
     assert_eq!(events.len(), 4);
     assert_eq!(events[0].id, "1");
-    assert_eq!(events[1].id, "2");
-    assert_eq!(events[2].id, "4");
-    assert_eq!(events[3].id, "5");
+    assert_eq!(events[1].id, "4");
+    assert_eq!(events[2].id, "5");
+    assert_eq!(events[3].id, "2");
+}
+
+#[allow(dead_code)]
+pub fn get_events_by_range_ordered(store: &mut impl NoteEventStore) {
+    store
+        .add_events(vec![
+            NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 20,
+                note_number: 64,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "1".to_string(),
+                start_ticks: 0,
+                end_ticks: 10,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "2".to_string(),
+                start_ticks: 5,
+                end_ticks: 10,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "3".to_string(),
+                start_ticks: 5,
+                end_ticks: 15,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+        ])
+        .unwrap();
+
+    let ids: Vec<&str> = store
+        .get_events_by_range_ordered(0, 20, NoteEventOrder::StartTicks)
+        .into_iter()
+        .map(|e| e.id.as_str())
+        .collect();
+
+    assert_eq!(ids, vec!["1", "0", "2", "3"]);
+
+    let ids: Vec<&str> = store
+        .get_events_by_range_ordered(0, 20, NoteEventOrder::EndTicks)
+        .into_iter()
+        .map(|e| e.id.as_str())
+        .collect();
+
+    assert_eq!(ids, vec!["1", "2", "3", "0"]);
+}
+
+#[allow(dead_code)]
+pub fn get_events_by_range_with(store: &mut impl NoteEventStore) {
+    store
+        .add_events(vec![
+            NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 5,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "1".to_string(),
+                start_ticks: 5,
+                end_ticks: 10,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "2".to_string(),
+                start_ticks: 10,
+                end_ticks: 15,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "3".to_string(),
+                start_ticks: 5,
+                end_ticks: 5,
+                note_number: 62,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+        ])
+        .unwrap();
+
+    let ids =
+        |events: Vec<&NoteEvent>| -> Vec<String> { events.iter().map(|e| e.id.clone()).collect() };
+
+    assert_eq!(
+        ids(store.get_events_by_range_with(5, 10, RangeSemantics::Closed)),
+        vec!["0", "1", "3", "2"]
+    );
+    assert_eq!(
+        ids(store.get_events_by_range_with(5, 10, RangeSemantics::HalfOpen)),
+        vec!["1", "3"]
+    );
+    assert_eq!(
+        ids(store.get_events_by_range_with(5, 10, RangeSemantics::StrictlyOverlapping)),
+        vec!["1"]
+    );
+
+    assert_eq!(
+        ids(store.get_events_by_range_with(5, 5, RangeSemantics::Closed)),
+        vec!["0", "1", "3"]
+    );
+    assert!(store
+        .get_events_by_range_with(5, 5, RangeSemantics::HalfOpen)
+        .is_empty());
+    assert!(store
+        .get_events_by_range_with(5, 5, RangeSemantics::StrictlyOverlapping)
+        .is_empty());
+
+    assert_eq!(
+        ids(store.get_events_by_range_with(4, 6, RangeSemantics::HalfOpen)),
+        vec!["0", "1", "3"]
+    );
+    assert_eq!(
+        ids(store.get_events_by_range_with(4, 6, RangeSemantics::StrictlyOverlapping)),
+        vec!["0", "1"]
+    );
+}
+
+#[allow(dead_code)]
+pub fn get_events_by_range_on_track(store: &mut impl NoteEventStore) {
+    store
+        .add_events(
+            (0..8)
+                .map(|i| NoteEvent {
+                    id: i.to_string(),
+                    start_ticks: i * 10,
+                    end_ticks: i * 10 + 15,
+                    note_number: 60,
+                    velocity: 100,
+                    channel: 0,
+                    track_id: (i % 2) as u16,
+                })
+                .collect(),
+        )
+        .unwrap();
+
+    let ids =
+        |events: Vec<&NoteEvent>| -> Vec<String> { events.iter().map(|e| e.id.clone()).collect() };
+
+    assert_eq!(
+        ids(store.get_events_by_range_on_track(0, 20, 40)),
+        vec!["2", "4"]
+    );
+    assert_eq!(
+        ids(store.get_events_by_range_on_track(1, 20, 40)),
+        vec!["1", "3"]
+    );
+    assert!(store.get_events_by_range_on_track(2, 0, 100).is_empty());
+}
+
+#[allow(dead_code)]
+pub fn iter_events_by_range(store: &mut impl NoteEventStore) {
+    store
+        .add_events(vec![
+            NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 4,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "1".to_string(),
+                start_ticks: 3,
+                end_ticks: 12,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "2".to_string(),
+                start_ticks: 6,
+                end_ticks: 8,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "3".to_string(),
+                start_ticks: 20,
+                end_ticks: 30,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+        ])
+        .unwrap();
+
+    let mut ids: Vec<&str> = store
+        .iter_events_by_range(5, 10)
+        .map(|e| e.id.as_str())
+        .collect();
+    ids.sort();
+
+    assert_eq!(ids, vec!["1", "2"]);
+    assert_eq!(store.iter_events_by_range(5, 10).take(1).count(), 1);
+    assert!(store.iter_events_by_range(13, 19).next().is_none());
+}
+
+#[allow(dead_code)]
+pub fn get_events_at(store: &mut impl NoteEventStore) {
+    store
+        .add_events(vec![
+            NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 10,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "1".to_string(),
+                start_ticks: 5,
+                end_ticks: 20,
+                note_number: 62,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "2".to_string(),
+                start_ticks: 10,
+                end_ticks: 15,
+                note_number: 64,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "3".to_string(),
+                start_ticks: 10,
+                end_ticks: 10,
+                note_number: 65,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+        ])
+        .unwrap();
+
+    let ids =
+        |events: Vec<&NoteEvent>| -> Vec<String> { events.iter().map(|e| e.id.clone()).collect() };
+
+    assert_eq!(ids(store.get_events_at(0)), vec!["0"]);
+    assert_eq!(ids(store.get_events_at(5)), vec!["0", "1"]);
+    // Event 0 ends and event 2 starts at tick 10, and the zero-length event 3
+    // never sounds.
+    assert_eq!(ids(store.get_events_at(10)), vec!["1", "2"]);
+    assert_eq!(ids(store.get_events_at(15)), vec!["1"]);
+    assert!(store.get_events_at(20).is_empty());
+    assert!(store.get_events_at(u64::MAX).is_empty());
+}
+
+#[allow(dead_code)]
+pub fn next_and_prev_event(store: &mut impl NoteEventStore) {
+    store
+        .add_events(vec![
+            NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 10,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "1".to_string(),
+                start_ticks: 10,
+                end_ticks: 20,
+                note_number: 64,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "2".to_string(),
+                start_ticks: 10,
+                end_ticks: 20,
+                note_number: 62,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "3".to_string(),
+                start_ticks: 30,
+                end_ticks: 40,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+        ])
+        .unwrap();
+
+    let id = |event: Option<&NoteEvent>| event.map(|e| e.id.clone());
+
+    assert_eq!(id(store.next_event_after(0)), Some("2".to_string()));
+    assert_eq!(id(store.next_event_after(10)), Some("3".to_string()));
+    assert_eq!(id(store.next_event_after(30)), None);
+
+    assert_eq!(id(store.prev_event_before(0)), None);
+    assert_eq!(id(store.prev_event_before(10)), Some("0".to_string()));
+    assert_eq!(id(store.prev_event_before(30)), Some("1".to_string()));
+    assert_eq!(id(store.prev_event_before(u64::MAX)), Some("3".to_string()));
+
+    assert_eq!(
+        id(store.next_event_with_pitch(0, 60)),
+        Some("3".to_string())
+    );
+    assert_eq!(
+        id(store.next_event_with_pitch(0, 64)),
+        Some("1".to_string())
+    );
+    assert_eq!(id(store.next_event_with_pitch(10, 64)), None);
+    assert_eq!(id(store.next_event_with_pitch(0, 127)), None);
+}
+
+#[allow(dead_code)]
+pub fn get_events_by_pitch(store: &mut impl NoteEventStore) {
+    store
+        .add_events(
+            (0..8)
+                .map(|i| NoteEvent {
+                    id: i.to_string(),
+                    start_ticks: 100 - i * 10,
+                    end_ticks: 100,
+                    note_number: 36 + (i % 4) as u8,
+                    velocity: 100,
+                    channel: 9,
+                    track_id: 0,
+                })
+                .collect(),
+        )
+        .unwrap();
+
+    let ids =
+        |events: Vec<&NoteEvent>| -> Vec<String> { events.iter().map(|e| e.id.clone()).collect() };
+
+    assert_eq!(ids(store.get_events_by_pitch(36)), vec!["4", "0"]);
+    assert_eq!(ids(store.get_events_by_pitch(38)), vec!["6", "2"]);
+    assert!(store.get_events_by_pitch(40).is_empty());
+    assert_eq!(
+        ids(store.get_events_by_pitch_range(37, 38)),
+        vec!["6", "5", "2", "1"]
+    );
+    assert!(store.get_events_by_pitch_range(38, 37).is_empty());
+    assert!(store.get_events_in_region(0, 100, 38, 37).is_empty());
+
+    store
+        .update_event(NoteEventUpdate {
+            id: "0".to_string(),
+            start_ticks: None,
+            end_ticks: None,
+            note_number: Some(40),
+            velocity: None,
+            channel: None,
+            track_id: None,
+        })
+        .unwrap();
+    store.delete_event("6").unwrap();
+
+    assert_eq!(ids(store.get_events_by_pitch(36)), vec!["4"]);
+    assert_eq!(ids(store.get_events_by_pitch(38)), vec!["2"]);
+    assert_eq!(ids(store.get_events_by_pitch(40)), vec!["0"]);
+}
+
+#[allow(dead_code)]
+pub fn query(store: &mut impl NoteEventStore) {
+    store
+        .add_events(
+            (0..12)
+                .map(|i| NoteEvent {
+                    id: i.to_string(),
+                    start_ticks: i * 10,
+                    end_ticks: i * 10 + 10,
+                    note_number: 60 + (i % 6) as u8 * 2,
+                    velocity: 20 + i as u8 * 10,
+                    channel: (i % 2) as u8,
+                    track_id: (i % 3) as u16,
+                })
+                .collect(),
+        )
+        .unwrap();
+
+    let ids =
+        |events: Vec<&NoteEvent>| -> Vec<String> { events.iter().map(|e| e.id.clone()).collect() };
+
+    assert_eq!(store.query().count(), 12);
+    assert_eq!(
+        ids(store
+            .query()
+            .range(0, 60)
+            .pitch(60..64)
+            .velocity(..40)
+            .collect()),
+        vec!["0", "1"]
+    );
+    assert_eq!(
+        ids(store.query().range(0, 60).velocity(..60).collect()),
+        vec!["0", "1", "2", "3"]
+    );
+    assert_eq!(
+        ids(store
+            .query()
+            .range(20, 60)
+            .semantics(RangeSemantics::HalfOpen)
+            .collect()),
+        vec!["2", "3", "4", "5"]
+    );
+    assert_eq!(ids(store.query().pitch(62..=62).collect()), vec!["1", "7"]);
+    assert_eq!(
+        ids(store.query().track(1).channel(1).collect()),
+        vec!["1", "7"]
+    );
+    assert_eq!(
+        ids(store.query().range(30, 80).track(2).collect()),
+        vec!["2", "5", "8"]
+    );
+    assert_eq!(
+        ids(store
+            .query()
+            .pitch(60..72)
+            .order(NoteEventOrder::EndTicks)
+            .collect()),
+        ids(store.get_events_by_pitch_range(60, 71))
+    );
+    assert!(store.query().pitch(..0).collect().is_empty());
+    assert!(store
+        .query()
+        .range(0, 200)
+        .velocity(200..)
+        .collect()
+        .is_empty());
+}
+
+#[allow(dead_code)]
+pub fn query_random(store: &mut impl NoteEventStore) {
+    let mut rng = StdRng::seed_from_u64(4);
+    let mut expected: Vec<NoteEvent> = Vec::new();
+
+    for i in 0..1000 {
+        let start_ticks = rng.gen_range(0..10000);
+        let event = NoteEvent {
+            id: i.to_string(),
+            start_ticks,
+            end_ticks: start_ticks + rng.gen_range(0..500),
+            note_number: rng.gen_range(36..84),
+            velocity: rng.gen_range(1..128),
+            channel: rng.gen_range(0..4),
+            track_id: rng.gen_range(0..4),
+        };
+        store.add_event(event.clone()).unwrap();
+        expected.push(event);
+    }
+
+    for _ in 0..100 {
+        let start_ticks = rng.gen_range(0..10500);
+        let end_ticks = start_ticks + rng.gen_range(0..1000);
+        let low_note = rng.gen_range(30..90);
+        let high_note = low_note + rng.gen_range(0..12);
+        let max_velocity = rng.gen_range(1..128);
+        let track_id = rng.gen_range(0..4);
+
+        let ticks = rng.gen_bool(0.5).then_some((start_ticks, end_ticks));
+        let notes = rng.gen_bool(0.5).then_some(low_note..=high_note);
+        let max_velocity = rng.gen_bool(0.5).then_some(max_velocity);
+        let track_id = rng.gen_bool(0.5).then_some(track_id);
+
+        let mut query = store.query();
+        if let Some((start_ticks, end_ticks)) = ticks {
+            query = query.range(start_ticks, end_ticks);
+        }
+        if let Some(notes) = notes.clone() {
+            query = query.pitch(notes);
+        }
+        if let Some(max_velocity) = max_velocity {
+            query = query.velocity(..max_velocity);
+        }
+        if let Some(track_id) = track_id {
+            query = query.track(track_id);
+        }
+
+        let mut expected_ids: Vec<&str> = expected
+            .iter()
+            .filter(|e| {
+                ticks.is_none_or(|(start_ticks, end_ticks)| {
+                    RangeSemantics::Closed.overlaps(e, start_ticks, end_ticks)
+                }) && notes
+                    .as_ref()
+                    .is_none_or(|notes| notes.contains(&e.note_number))
+                    && max_velocity.is_none_or(|max_velocity| e.velocity < max_velocity)
+                    && track_id.is_none_or(|track_id| e.track_id == track_id)
+            })
+            .map(|e| e.id.as_str())
+            .collect();
+        expected_ids.sort_unstable();
+        let mut ids: Vec<&str> = query.iter().map(|e| e.id.as_str()).collect();
+        ids.sort_unstable();
+
+        assert_eq!(ids, expected_ids);
+    }
+}
+
+#[allow(dead_code)]
+pub fn aggregates_in_range(store: &mut impl NoteEventStore) {
+    store
+        .add_events(vec![
+            NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 100,
+                note_number: 48,
+                velocity: 30,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "1".to_string(),
+                start_ticks: 50,
+                end_ticks: 100,
+                note_number: 60,
+                velocity: 90,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "2".to_string(),
+                start_ticks: 100,
+                end_ticks: 150,
+                note_number: 72,
+                velocity: 60,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "3".to_string(),
+                start_ticks: 120,
+                end_ticks: 120,
+                note_number: 84,
+                velocity: 127,
+                channel: 0,
+                track_id: 0,
+            },
+        ])
+        .unwrap();
+
+    assert_eq!(store.count_events_in_range(0, 200), 4);
+    assert_eq!(store.count_events_in_range(100, 100), 3);
+    assert_eq!(store.count_events_in_range(151, 200), 0);
+
+    assert_eq!(store.max_polyphony_in_range(0, 200), 2);
+    // Events ending at 100 have stopped sounding when the next one starts.
+    assert_eq!(store.max_polyphony_in_range(100, 200), 1);
+    assert_eq!(store.max_polyphony_in_range(0, 49), 1);
+    assert_eq!(store.max_polyphony_in_range(150, 200), 0);
+    assert_eq!(store.max_polyphony_in_range(60, 40), 0);
+
+    assert_eq!(store.pitch_bounds_in_range(0, 200), Some((48, 84)));
+    assert_eq!(store.pitch_bounds_in_range(50, 99), Some((48, 60)));
+    assert_eq!(store.pitch_bounds_in_range(110, 130), Some((72, 84)));
+    assert_eq!(store.pitch_bounds_in_range(151, 200), None);
+
+    let stats = store.velocity_stats_in_range(0, 99).unwrap();
+    assert_eq!(
+        stats,
+        VelocityStats {
+            count: 2,
+            min: 30,
+            max: 90,
+            sum: 120,
+        }
+    );
+    assert_eq!(stats.mean(), 60.0);
+    assert_eq!(store.velocity_stats_in_range(151, 200), None);
+}
+
+#[allow(dead_code)]
+pub fn aggregates_in_range_random(store: &mut impl NoteEventStore) {
+    let mut rng = StdRng::seed_from_u64(5);
+    let mut expected: Vec<NoteEvent> = Vec::new();
+
+    for i in 0..1000 {
+        let start_ticks = rng.gen_range(0..10000);
+        let event = NoteEvent {
+            id: i.to_string(),
+            start_ticks,
+            end_ticks: start_ticks + rng.gen_range(0..500),
+            note_number: rng.gen_range(36..84),
+            velocity: rng.gen_range(1..128),
+            channel: 0,
+            track_id: 0,
+        };
+        store.add_event(event.clone()).unwrap();
+        expected.push(event);
+    }
+
+    for _ in 0..100 {
+        let start_ticks = rng.gen_range(0..10500);
+        let end_ticks = start_ticks + rng.gen_range(0..1000);
+
+        let in_range: Vec<&NoteEvent> = expected
+            .iter()
+            .filter(|e| RangeSemantics::Closed.overlaps(e, start_ticks, end_ticks))
+            .collect();
+        // The most events sound at once right as one of them starts.
+        let max_polyphony = in_range
+            .iter()
+            .map(|e| e.start_ticks.max(start_ticks))
+            .map(|ticks| {
+                in_range
+                    .iter()
+                    .filter(|e| e.start_ticks <= ticks && ticks < e.end_ticks)
+                    .count()
+            })
+            .max()
+            .unwrap_or(0);
+
+        assert_eq!(
+            store.count_events_in_range(start_ticks, end_ticks),
+            in_range.len()
+        );
+        assert_eq!(
+            store.max_polyphony_in_range(start_ticks, end_ticks),
+            max_polyphony
+        );
+        assert_eq!(
+            store.pitch_bounds_in_range(start_ticks, end_ticks),
+            in_range
+                .iter()
+                .map(|e| e.note_number)
+                .min()
+                .zip(in_range.iter().map(|e| e.note_number).max())
+        );
+        assert_eq!(
+            store.velocity_stats_in_range(start_ticks, end_ticks),
+            VelocityStats::from_velocities(in_range.iter().map(|e| e.velocity))
+        );
+    }
+}
+
+#[allow(dead_code)]
+pub fn get_events_in_region(store: &mut impl NoteEventStore) {
+    store
+        .add_events(vec![
+            NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 10,
+                note_number: 59,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "1".to_string(),
+                start_ticks: 5,
+                end_ticks: 15,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "2".to_string(),
+                start_ticks: 0,
+                end_ticks: 10,
+                note_number: 72,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "3".to_string(),
+                start_ticks: 20,
+                end_ticks: 30,
+                note_number: 65,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "4".to_string(),
+                start_ticks: 8,
+                end_ticks: 9,
+                note_number: 73,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+        ])
+        .unwrap();
+
+    let ids: Vec<&str> = store
+        .get_events_in_region(0, 10, 60, 72)
+        .into_iter()
+        .map(|e| e.id.as_str())
+        .collect();
+
+    assert_eq!(ids, vec!["2", "1"]);
+
+    store
+        .update_event(NoteEventUpdate {
+            id: "4".to_string(),
+            start_ticks: None,
+            end_ticks: None,
+            note_number: Some(61),
+            velocity: None,
+            channel: None,
+            track_id: None,
+        })
+        .unwrap();
+    store.delete_event("2").unwrap();
+
+    let ids: Vec<&str> = store
+        .get_events_in_region(0, 10, 60, 72)
+        .into_iter()
+        .map(|e| e.id.as_str())
+        .collect();
+
+    assert_eq!(ids, vec!["1", "4"]);
+}
+
+#[allow(dead_code)]
+pub fn get_events_by_time_range(store: &mut impl NoteEventStore) {
+    let mut tempo_map = TempoMap::new(480, 120.0).unwrap();
+    tempo_map
+        .insert_change(TempoChange {
+            ticks: 960,
+            bpm: 60.0,
+            curve: TempoCurve::Constant,
+        })
+        .unwrap();
+
+    store
+        .add_events(
+            (0..4)
+                .map(|i| NoteEvent {
+                    id: i.to_string(),
+                    start_ticks: i * 480,
+                    end_ticks: (i + 1) * 480,
+                    note_number: 60,
+                    velocity: 100,
+                    channel: 0,
+                    track_id: 0,
+                })
+                .collect(),
+        )
+        .unwrap();
+
+    let ids =
+        |events: Vec<&NoteEvent>| -> Vec<String> { events.iter().map(|e| e.id.clone()).collect() };
+
+    // Ticks 576 to 1200.
+    assert_eq!(
+        ids(store.get_events_by_time_range(&tempo_map, 0.6, 1.5)),
+        vec!["1", "2"]
+    );
+    // Tick 1440.
+    assert_eq!(
+        ids(store.get_events_by_time_range(&tempo_map, 2.0, 2.0)),
+        vec!["2", "3"]
+    );
+    // Between ticks 672 and 673.
+    assert_eq!(
+        ids(store.get_events_by_time_range(&tempo_map, 0.7001, 0.7002)),
+        vec!["1"]
+    );
+    assert!(store
+        .get_events_by_time_range(&tempo_map, 4.5, 5.0)
+        .is_empty());
+}
+
+#[allow(dead_code)]
+pub fn get_events_in_bars(store: &mut impl NoteEventStore) {
+    let mut meter_map = MeterMap::new(
+        480,
+        TimeSignature {
+            numerator: 4,
+            denominator: 4,
+        },
+    )
+    .unwrap();
+    // Bars 1-2 are 1920 ticks long, bar 3 onwards 1440 ticks.
+    meter_map
+        .insert_change(MeterChange {
+            bar: 3,
+            time_signature: TimeSignature {
+                numerator: 3,
+                denominator: 4,
+            },
+        })
+        .unwrap();
+
+    store
+        .add_events(vec![
+            NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 1919,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "1".to_string(),
+                start_ticks: 1920,
+                end_ticks: 2400,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "2".to_string(),
+                start_ticks: 3000,
+                end_ticks: 4000,
+                note_number: 62,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+            NoteEvent {
+                id: "3".to_string(),
+                start_ticks: 5280,
+                end_ticks: 5400,
+                note_number: 64,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            },
+        ])
+        .unwrap();
+
+    let ids =
+        |events: Vec<&NoteEvent>| -> Vec<String> { events.iter().map(|e| e.id.clone()).collect() };
+
+    assert_eq!(ids(store.get_events_in_bars(&meter_map, 1..=1)), vec!["0"]);
+    assert_eq!(ids(store.get_events_in_bars(&meter_map, 0..=0)), vec!["0"]);
+    assert_eq!(
+        ids(store.get_events_in_bars(&meter_map, 2..=3)),
+        vec!["1", "2"]
+    );
+    // Bar 4 starts at tick 5280.
+    assert_eq!(ids(store.get_events_in_bars(&meter_map, 4..=8)), vec!["3"]);
+    assert!(store.get_events_in_bars(&meter_map, 5..=8).is_empty());
+    #[allow(clippy::reversed_empty_ranges)]
+    let empty = 3..=2;
+    assert!(store.get_events_in_bars(&meter_map, empty).is_empty());
+}
+
+fn add_random_events(
+    store: &mut impl NoteEventStore,
+    rng: &mut StdRng,
+) -> HashMap<String, NoteEvent> {
+    let mut expected: HashMap<String, NoteEvent> = HashMap::new();
+
+    for i in 0..1000 {
+        let start_ticks = rng.gen_range(0..10000);
+        let event = NoteEvent {
+            id: i.to_string(),
+            start_ticks,
+            end_ticks: start_ticks + rng.gen_range(0..500),
+            note_number: rng.gen_range(36..84),
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        };
+        store.add_event(event.clone()).unwrap();
+        expected.insert(event.id.clone(), event);
+    }
+
+    for i in (0..1000).step_by(3) {
+        store.delete_event(&i.to_string()).unwrap();
+        expected.remove(&i.to_string());
+    }
+
+    for i in (1..1000).step_by(7) {
+        let Some(event) = expected.get_mut(&i.to_string()) else {
+            continue;
+        };
+        let start_ticks = rng.gen_range(0..10000);
+        let end_ticks = start_ticks + rng.gen_range(0..500);
+        let note_number = rng.gen_range(36..84);
+        store
+            .update_event(NoteEventUpdate {
+                id: i.to_string(),
+                start_ticks: Some(start_ticks),
+                end_ticks: Some(end_ticks),
+                note_number: Some(note_number),
+                velocity: None,
+                channel: None,
+                track_id: None,
+            })
+            .unwrap();
+        event.start_ticks = start_ticks;
+        event.end_ticks = end_ticks;
+        event.note_number = note_number;
+    }
+
+    expected
+}
+
+#[allow(dead_code)]
+pub fn get_events_by_range_random(store: &mut impl NoteEventStore) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let expected = add_random_events(store, &mut rng);
+
+    for _ in 0..100 {
+        let start_ticks = rng.gen_range(0..10500);
+        let end_ticks = start_ticks + rng.gen_range(0..1000);
+
+        let ids: Vec<&str> = store
+            .get_events_by_range(start_ticks, end_ticks)
+            .into_iter()
+            .map(|e| e.id.as_str())
+            .collect();
+
+        let mut expected_events: Vec<&NoteEvent> = expected
+            .values()
+            .filter(|e| e.end_ticks >= start_ticks && e.start_ticks <= end_ticks)
+            .collect();
+        expected_events.sort_by(|a, b| NoteEventOrder::StartTicks.compare(a, b));
+        let expected_ids: Vec<&str> = expected_events.iter().map(|e| e.id.as_str()).collect();
+
+        assert_eq!(ids, expected_ids);
+    }
+}
+
+#[allow(dead_code)]
+pub fn get_events_by_range_with_random(store: &mut impl NoteEventStore) {
+    let mut rng = StdRng::seed_from_u64(3);
+    let expected = add_random_events(store, &mut rng);
+
+    for _ in 0..100 {
+        let start_ticks = rng.gen_range(0..10500);
+        let end_ticks = start_ticks + rng.gen_range(0..1000);
+
+        for semantics in [
+            RangeSemantics::Closed,
+            RangeSemantics::HalfOpen,
+            RangeSemantics::StrictlyOverlapping,
+        ] {
+            // Spelled out rather than calling `RangeSemantics::overlaps`, so a
+            // bug there fails this test.
+            let matches = |e: &NoteEvent| match semantics {
+                RangeSemantics::Closed => e.start_ticks <= end_ticks && e.end_ticks >= start_ticks,
+                RangeSemantics::HalfOpen if e.start_ticks == e.end_ticks => {
+                    start_ticks <= e.start_ticks && e.start_ticks < end_ticks
+                }
+                RangeSemantics::HalfOpen => e.start_ticks < end_ticks && e.end_ticks > start_ticks,
+                RangeSemantics::StrictlyOverlapping => {
+                    e.start_ticks < end_ticks
+                        && e.end_ticks > start_ticks
+                        && start_ticks < end_ticks
+                        && e.start_ticks < e.end_ticks
+                }
+            };
+            let mut expected_ids: Vec<&str> = expected
+                .values()
+                .filter(|e| matches(e))
+                .map(|e| e.id.as_str())
+                .collect();
+            expected_ids.sort_unstable();
+            let mut ids: Vec<&str> = store
+                .iter_events_by_range_with(start_ticks, end_ticks, semantics)
+                .map(|e| e.id.as_str())
+                .collect();
+            ids.sort_unstable();
+
+            assert_eq!(ids, expected_ids);
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub fn get_events_at_random(store: &mut impl NoteEventStore) {
+    let mut rng = StdRng::seed_from_u64(2);
+    let expected = add_random_events(store, &mut rng);
+
+    for _ in 0..100 {
+        let ticks = rng.gen_range(0..11000);
+
+        let mut expected_ids: Vec<&str> = expected
+            .values()
+            .filter(|e| e.start_ticks <= ticks && ticks < e.end_ticks)
+            .map(|e| e.id.as_str())
+            .collect();
+        expected_ids.sort_unstable();
+        let mut ids: Vec<&str> = store.iter_events_at(ticks).map(|e| e.id.as_str()).collect();
+        ids.sort_unstable();
+
+        assert_eq!(ids, expected_ids);
+    }
+}
+
+#[allow(dead_code)]
+pub fn get_events_in_region_random(store: &mut impl NoteEventStore) {
+    let mut rng = StdRng::seed_from_u64(1);
+    let expected = add_random_events(store, &mut rng);
+
+    for _ in 0..100 {
+        let start_ticks = rng.gen_range(0..10500);
+        let end_ticks = start_ticks + rng.gen_range(0..1000);
+        let low_note = rng.gen_range(30..90);
+        let high_note = low_note + rng.gen_range(0..12);
+
+        let ids: Vec<&str> = store
+            .get_events_in_region(start_ticks, end_ticks, low_note, high_note)
+            .into_iter()
+            .map(|e| e.id.as_str())
+            .collect();
+
+        let mut expected_events: Vec<&NoteEvent> = expected
+            .values()
+            .filter(|e| {
+                e.end_ticks >= start_ticks
+                    && e.start_ticks <= end_ticks
+                    && (low_note..=high_note).contains(&e.note_number)
+            })
+            .collect();
+        expected_events.sort_by(|a, b| NoteEventOrder::StartTicks.compare(a, b));
+        let expected_ids: Vec<&str> = expected_events.iter().map(|e| e.id.as_str()).collect();
+
+        assert_eq!(ids, expected_ids);
+    }
 }
@@ -0,0 +1,100 @@
+use super::{
+    base::NoteEventStore,
+    error::NoteEventStoreError,
+    event::{NoteEvent, NoteEventUpdate},
+    operation::NoteEventOperation,
+};
+
+pub struct NoteEventTransaction<'a, S: NoteEventStore> {
+    store: &'a mut S,
+    undo_log: Vec<NoteEventOperation>,
+}
+
+impl<'a, S: NoteEventStore> NoteEventTransaction<'a, S> {
+    pub(crate) fn new(store: &'a mut S) -> Self {
+        NoteEventTransaction {
+            store,
+            undo_log: vec![],
+        }
+    }
+
+    pub fn add_event(&mut self, event: NoteEvent) -> Result<(), NoteEventStoreError> {
+        self.apply(NoteEventOperation::Add(event))?;
+        Ok(())
+    }
+
+    pub fn update_event(&mut self, event: NoteEventUpdate) -> Result<(), NoteEventStoreError> {
+        self.apply(NoteEventOperation::Update(event))?;
+        Ok(())
+    }
+
+    pub fn delete_event(&mut self, id: &str) -> Result<NoteEvent, NoteEventStoreError> {
+        match self.apply(NoteEventOperation::Delete(id.to_string()))? {
+            NoteEventOperation::Add(event) => Ok(event),
+            _ => unreachable!("the inverse of a delete is an add"),
+        }
+    }
+
+    pub fn get_event(&self, id: &str) -> Option<&NoteEvent> {
+        self.store.get_event(id)
+    }
+
+    /// Applies `operation` and returns the operation that reverts it.
+    pub fn apply(
+        &mut self,
+        operation: NoteEventOperation,
+    ) -> Result<NoteEventOperation, NoteEventStoreError> {
+        let inverse = operation.apply(self.store)?;
+        self.undo_log.push(inverse.clone());
+        Ok(inverse)
+    }
+
+    /// Reverts the applied operations, newest first, and stops at the first
+    /// revert that fails.
+    pub(crate) fn rollback(self) -> Result<(), NoteEventStoreError> {
+        for operation in self.undo_log.into_iter().rev() {
+            operation.apply(self.store)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::note_event_store::test_util::FailingDeleteNoteEventStore;
+
+    use super::*;
+
+    fn note_event(id: &str) -> NoteEvent {
+        NoteEvent {
+            id: id.to_string(),
+            start_ticks: 0,
+            end_ticks: 10,
+            note_number: 60,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+        }
+    }
+
+    #[test]
+    fn test_failed_rollback() {
+        let failing_id = Rc::new(RefCell::new(Some("1".to_string())));
+        let mut store = FailingDeleteNoteEventStore::new(failing_id);
+
+        let result = store.add_events(vec![note_event("0"), note_event("1"), note_event("1")]);
+
+        assert_eq!(
+            result,
+            Err(NoteEventStoreError::rollback_failed(
+                NoteEventStoreError::DuplicateId("1".to_string()),
+                NoteEventStoreError::NotFound("1".to_string()),
+            ))
+        );
+        // Reverting stopped at "1", so "0" was never reverted either.
+        assert!(store.get_event("0").is_some());
+        assert!(store.get_event("1").is_some());
+    }
+}
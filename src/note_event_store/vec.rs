@@ -1,8 +1,10 @@
 use super::{
     base::NoteEventStore,
+    error::NoteEventStoreError,
     event::{NoteEvent, NoteEventUpdate},
 };
 
+#[derive(Default)]
 pub struct VecNoteEventStore {
     store: Vec<NoteEvent>,
 }
@@ -14,54 +16,77 @@ impl VecNoteEventStore {
 }
 
 impl NoteEventStore for VecNoteEventStore {
-    fn add_event(&mut self, event: NoteEvent) {
+    fn add_event(&mut self, event: NoteEvent) -> Result<(), NoteEventStoreError> {
+        if self.store.iter().any(|e| e.id == event.id) {
+            return Err(NoteEventStoreError::DuplicateId(event.id));
+        }
+        NoteEventStoreError::check_range(&event.id, event.start_ticks, event.end_ticks)?;
+
         self.store.push(event);
+        Ok(())
     }
 
-    fn add_events(&mut self, events: Vec<NoteEvent>) {
-        self.store.extend(events);
-    }
-
-    fn update_event(&mut self, event: NoteEventUpdate) {
-        if let Some(existing_event) = self.store.iter_mut().find(|e| e.id == event.id) {
-            if let Some(start_ticks) = event.start_ticks {
-                existing_event.start_ticks = start_ticks;
-            }
-            if let Some(end_ticks) = event.end_ticks {
-                existing_event.end_ticks = end_ticks;
-            }
-            if let Some(note_number) = event.note_number {
-                existing_event.note_number = note_number;
-            }
-            if let Some(velocity) = event.velocity {
-                existing_event.velocity = velocity;
-            }
+    fn update_event(&mut self, event: NoteEventUpdate) -> Result<(), NoteEventStoreError> {
+        let Some(existing_event) = self.store.iter_mut().find(|e| e.id == event.id) else {
+            return Err(NoteEventStoreError::NotFound(event.id));
+        };
+
+        NoteEventStoreError::check_range(
+            &event.id,
+            event.start_ticks.unwrap_or(existing_event.start_ticks),
+            event.end_ticks.unwrap_or(existing_event.end_ticks),
+        )?;
+
+        if let Some(start_ticks) = event.start_ticks {
+            existing_event.start_ticks = start_ticks;
+        }
+        if let Some(end_ticks) = event.end_ticks {
+            existing_event.end_ticks = end_ticks;
+        }
+        if let Some(note_number) = event.note_number {
+            existing_event.note_number = note_number;
+        }
+        if let Some(velocity) = event.velocity {
+            existing_event.velocity = velocity;
+        }
+        if let Some(channel) = event.channel {
+            existing_event.channel = channel;
         }
+        if let Some(track_id) = event.track_id {
+            existing_event.track_id = track_id;
+        }
+        Ok(())
     }
 
-    fn update_events(&mut self, events: Vec<NoteEventUpdate>) {
-        for event in events {
-            self.update_event(event);
+    fn delete_event(&mut self, id: &str) -> Result<NoteEvent, NoteEventStoreError> {
+        match self.store.iter().position(|e| e.id == id) {
+            Some(index) => Ok(self.store.remove(index)),
+            None => Err(NoteEventStoreError::NotFound(id.to_string())),
         }
     }
 
-    fn delete_event(&mut self, id: &str) {
-        self.store.retain(|e| e.id != id);
+    fn get_event(&self, id: &str) -> Option<&NoteEvent> {
+        self.store.iter().find(|e| e.id == id)
     }
 
-    fn delete_events(&mut self, ids: Vec<&str>) {
-        self.store.retain(|e| !ids.contains(&e.id.as_str()));
+    fn iter_events(&self) -> impl Iterator<Item = &NoteEvent> {
+        self.store.iter()
     }
 
-    fn get_event(&self, id: &str) -> Option<&NoteEvent> {
-        self.store.iter().find(|e| e.id == id)
+    fn iter_events_by_range(
+        &self,
+        start_ticks: u64,
+        end_ticks: u64,
+    ) -> impl Iterator<Item = &NoteEvent> {
+        self.store
+            .iter()
+            .filter(move |e| e.end_ticks >= start_ticks && e.start_ticks <= end_ticks)
     }
 
-    fn get_events_by_range(&self, start_ticks: u64, end_ticks: u64) -> Vec<&NoteEvent> {
+    fn iter_events_at(&self, ticks: u64) -> impl Iterator<Item = &NoteEvent> {
         self.store
             .iter()
-            .filter(|e| e.end_ticks >= start_ticks && e.start_ticks <= end_ticks)
-            .collect()
+            .filter(move |e| e.start_ticks <= ticks && ticks < e.end_ticks)
     }
 }
 
@@ -83,6 +108,18 @@ mod tests {
         test_util::add_events(&mut store);
     }
 
+    #[test]
+    fn test_add_event_duplicate_id() {
+        let mut store = VecNoteEventStore::new();
+        test_util::add_event_duplicate_id(&mut store);
+    }
+
+    #[test]
+    fn test_add_event_invalid_range() {
+        let mut store = VecNoteEventStore::new();
+        test_util::add_event_invalid_range(&mut store);
+    }
+
     #[test]
     fn test_update_event_all_fields() {
         let mut store = VecNoteEventStore::new();
@@ -107,6 +144,12 @@ mod tests {
         test_util::update_event_not_found(&mut store);
     }
 
+    #[test]
+    fn test_update_event_invalid_range() {
+        let mut store = VecNoteEventStore::new();
+        test_util::update_event_invalid_range(&mut store);
+    }
+
     #[test]
     fn test_update_events() {
         let mut store = VecNoteEventStore::new();
@@ -131,15 +174,165 @@ mod tests {
         test_util::delete_events(&mut store);
     }
 
+    #[test]
+    fn test_delete_events_on_track() {
+        let mut store = VecNoteEventStore::new();
+        test_util::delete_events_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_add_events_atomic() {
+        let mut store = VecNoteEventStore::new();
+        test_util::add_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_update_events_atomic() {
+        let mut store = VecNoteEventStore::new();
+        test_util::update_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_delete_events_atomic() {
+        let mut store = VecNoteEventStore::new();
+        test_util::delete_events_atomic(&mut store);
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut store = VecNoteEventStore::new();
+        test_util::transaction(&mut store);
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        let mut store = VecNoteEventStore::new();
+        test_util::transaction_rollback(&mut store);
+    }
+
     #[test]
     fn test_get_event() {
         let mut store = VecNoteEventStore::new();
         test_util::get_event(&mut store);
     }
 
+    #[test]
+    fn test_iter_events() {
+        let mut store = VecNoteEventStore::new();
+        test_util::iter_events(&mut store);
+    }
+
     #[test]
     fn test_get_events_by_range() {
         let mut store = VecNoteEventStore::new();
         test_util::get_events_by_range(&mut store);
     }
+
+    #[test]
+    fn test_get_events_by_range_ordered() {
+        let mut store = VecNoteEventStore::new();
+        test_util::get_events_by_range_ordered(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with() {
+        let mut store = VecNoteEventStore::new();
+        test_util::get_events_by_range_with(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_on_track() {
+        let mut store = VecNoteEventStore::new();
+        test_util::get_events_by_range_on_track(&mut store);
+    }
+
+    #[test]
+    fn test_iter_events_by_range() {
+        let mut store = VecNoteEventStore::new();
+        test_util::iter_events_by_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_random() {
+        let mut store = VecNoteEventStore::new();
+        test_util::get_events_by_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at() {
+        let mut store = VecNoteEventStore::new();
+        test_util::get_events_at(&mut store);
+    }
+
+    #[test]
+    fn test_next_and_prev_event() {
+        let mut store = VecNoteEventStore::new();
+        test_util::next_and_prev_event(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_pitch() {
+        let mut store = VecNoteEventStore::new();
+        test_util::get_events_by_pitch(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range() {
+        let mut store = VecNoteEventStore::new();
+        test_util::aggregates_in_range(&mut store);
+    }
+
+    #[test]
+    fn test_aggregates_in_range_random() {
+        let mut store = VecNoteEventStore::new();
+        test_util::aggregates_in_range_random(&mut store);
+    }
+
+    #[test]
+    fn test_query() {
+        let mut store = VecNoteEventStore::new();
+        test_util::query(&mut store);
+    }
+
+    #[test]
+    fn test_query_random() {
+        let mut store = VecNoteEventStore::new();
+        test_util::query_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region() {
+        let mut store = VecNoteEventStore::new();
+        test_util::get_events_in_region(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_time_range() {
+        let mut store = VecNoteEventStore::new();
+        test_util::get_events_by_time_range(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_bars() {
+        let mut store = VecNoteEventStore::new();
+        test_util::get_events_in_bars(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_at_random() {
+        let mut store = VecNoteEventStore::new();
+        test_util::get_events_at_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_by_range_with_random() {
+        let mut store = VecNoteEventStore::new();
+        test_util::get_events_by_range_with_random(&mut store);
+    }
+
+    #[test]
+    fn test_get_events_in_region_random() {
+        let mut store = VecNoteEventStore::new();
+        test_util::get_events_in_region_random(&mut store);
+    }
 }
@@ -0,0 +1,53 @@
+use std::{error::Error, fmt};
+
+use crate::note_event_store::error::NoteEventStoreError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SmfError {
+    UnexpectedEof,
+    MissingHeader,
+    UnsupportedFormat(u16),
+    UnsupportedTimeDivision(u16),
+    MissingStatus,
+    InvalidStatus(u8),
+    DeltaTimeOverflow(u64),
+    TooManyTracks,
+    Store(NoteEventStoreError),
+}
+
+impl fmt::Display for SmfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SmfError::UnexpectedEof => write!(f, "unexpected end of file"),
+            SmfError::MissingHeader => write!(f, "file does not start with an MThd chunk"),
+            SmfError::UnsupportedFormat(format) => write!(f, "unsupported SMF format {}", format),
+            SmfError::UnsupportedTimeDivision(division) => {
+                write!(f, "unsupported SMPTE time division {:#06x}", division)
+            }
+            SmfError::MissingStatus => write!(f, "data byte without a running status"),
+            SmfError::InvalidStatus(status) => write!(f, "invalid status byte {:#04x}", status),
+            SmfError::DeltaTimeOverflow(delta_ticks) => write!(
+                f,
+                "delta time of {} ticks does not fit in a variable-length quantity",
+                delta_ticks
+            ),
+            SmfError::TooManyTracks => write!(f, "track ids do not fit in 65535 tracks"),
+            SmfError::Store(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for SmfError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SmfError::Store(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<NoteEventStoreError> for SmfError {
+    fn from(error: NoteEventStoreError) -> Self {
+        SmfError::Store(error)
+    }
+}
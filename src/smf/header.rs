@@ -0,0 +1,29 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmfFormat {
+    SingleTrack,
+    MultiTrack,
+}
+
+impl SmfFormat {
+    pub fn from_u16(format: u16) -> Option<SmfFormat> {
+        match format {
+            0 => Some(SmfFormat::SingleTrack),
+            1 => Some(SmfFormat::MultiTrack),
+            _ => None,
+        }
+    }
+
+    pub fn to_u16(self) -> u16 {
+        match self {
+            SmfFormat::SingleTrack => 0,
+            SmfFormat::MultiTrack => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmfHeader {
+    pub format: SmfFormat,
+    pub track_count: u16,
+    pub ppq: u16,
+}
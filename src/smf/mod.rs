@@ -0,0 +1,4 @@
+pub mod error;
+pub mod header;
+pub mod reader;
+pub mod writer;
@@ -0,0 +1,591 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::note_event_store::{
+    base::NoteEventStore,
+    control_event::{ControlEvent, ControlEventStore, ControlMessage},
+    error::NoteEventStoreError,
+    event::NoteEvent,
+};
+
+use super::{
+    error::SmfError,
+    header::{SmfFormat, SmfHeader},
+};
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Cursor { bytes, position: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], SmfError> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(SmfError::UnexpectedEof)?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, SmfError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, SmfError> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, SmfError> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_variable_length(&mut self) -> Result<u32, SmfError> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.read_u8()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(SmfError::UnexpectedEof)
+    }
+
+    fn read_chunk(&mut self) -> Result<(&'a [u8], &'a [u8]), SmfError> {
+        let id = self.read_bytes(4)?;
+        let len = self.read_u32()? as usize;
+        let data = self.read_bytes(len)?;
+        Ok((id, data))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Smf {
+    pub header: SmfHeader,
+    pub note_events: Vec<NoteEvent>,
+    pub control_events: Vec<ControlEvent>,
+}
+
+impl Smf {
+    pub fn parse(bytes: &[u8]) -> Result<Smf, SmfError> {
+        let mut cursor = Cursor::new(bytes);
+
+        let (id, data) = cursor.read_chunk()?;
+        if id != b"MThd" {
+            return Err(SmfError::MissingHeader);
+        }
+        let header = parse_header(data)?;
+
+        let mut note_events = vec![];
+        let mut control_events = vec![];
+        let mut track_index = 0;
+        while !cursor.is_empty() && track_index < header.track_count {
+            let (id, data) = cursor.read_chunk()?;
+            // Unknown chunk types must be skipped.
+            if id != b"MTrk" {
+                continue;
+            }
+            parse_track(data, track_index, &mut note_events, &mut control_events)?;
+            track_index += 1;
+        }
+
+        Ok(Smf {
+            header,
+            note_events,
+            control_events,
+        })
+    }
+}
+
+fn parse_header(data: &[u8]) -> Result<SmfHeader, SmfError> {
+    let mut cursor = Cursor::new(data);
+    let format = cursor.read_u16()?;
+    let track_count = cursor.read_u16()?;
+    let division = cursor.read_u16()?;
+
+    let format = SmfFormat::from_u16(format).ok_or(SmfError::UnsupportedFormat(format))?;
+    if division & 0x8000 != 0 {
+        return Err(SmfError::UnsupportedTimeDivision(division));
+    }
+
+    Ok(SmfHeader {
+        format,
+        track_count,
+        ppq: division,
+    })
+}
+
+struct HeldNote {
+    id: String,
+    start_ticks: u64,
+    velocity: u8,
+}
+
+fn parse_track(
+    data: &[u8],
+    track_index: u16,
+    note_events: &mut Vec<NoteEvent>,
+    control_events: &mut Vec<ControlEvent>,
+) -> Result<(), SmfError> {
+    let mut cursor = Cursor::new(data);
+    let mut ticks = 0u64;
+    let mut running_status: Option<u8> = None;
+    let mut note_count = 0usize;
+    let mut control_count = 0usize;
+    // Notes still held, keyed by (channel, note number). Repeated note-ons of
+    // the same pitch are closed by note-offs in first-in, first-out order.
+    let mut sounding: HashMap<(u8, u8), VecDeque<HeldNote>> = HashMap::new();
+
+    while !cursor.is_empty() {
+        ticks += cursor.read_variable_length()? as u64;
+
+        let byte = cursor.read_u8()?;
+        let (status, first_data) = if byte & 0x80 != 0 {
+            (byte, None)
+        } else {
+            (running_status.ok_or(SmfError::MissingStatus)?, Some(byte))
+        };
+
+        match status {
+            0xFF => {
+                running_status = None;
+                let meta_type = cursor.read_u8()?;
+                let len = cursor.read_variable_length()? as usize;
+                cursor.read_bytes(len)?;
+                // End of track.
+                if meta_type == 0x2F {
+                    break;
+                }
+            }
+            0xF0 | 0xF7 => {
+                running_status = None;
+                let len = cursor.read_variable_length()? as usize;
+                cursor.read_bytes(len)?;
+            }
+            0x80..=0xEF => {
+                running_status = Some(status);
+                let data_1 = match first_data {
+                    Some(data_1) => data_1,
+                    None => cursor.read_u8()?,
+                };
+                let data_2 = match status & 0xF0 {
+                    0xC0 | 0xD0 => 0,
+                    _ => cursor.read_u8()?,
+                };
+                let channel = status & 0x0F;
+
+                let control_message = match status & 0xF0 {
+                    0xA0 => Some(ControlMessage::PolyAftertouch {
+                        note_number: data_1,
+                        pressure: data_2,
+                    }),
+                    0xB0 => Some(ControlMessage::ControlChange {
+                        controller: data_1,
+                        value: data_2,
+                    }),
+                    0xC0 => Some(ControlMessage::ProgramChange(data_1)),
+                    0xD0 => Some(ControlMessage::ChannelPressure(data_1)),
+                    0xE0 => Some(ControlMessage::PitchBend(
+                        (data_2 as u16) << 7 | data_1 as u16,
+                    )),
+                    _ => None,
+                };
+                if let Some(message) = control_message {
+                    control_events.push(ControlEvent {
+                        id: format!("{}-c{}", track_index, control_count),
+                        ticks,
+                        message,
+                        channel,
+                        track_id: track_index,
+                    });
+                    control_count += 1;
+                }
+
+                match status & 0xF0 {
+                    0x90 if data_2 > 0 => {
+                        let id = format!("{}-{}", track_index, note_count);
+                        note_count += 1;
+                        sounding
+                            .entry((channel, data_1))
+                            .or_default()
+                            .push_back(HeldNote {
+                                id,
+                                start_ticks: ticks,
+                                velocity: data_2,
+                            });
+                    }
+                    0x80 | 0x90 => {
+                        if let Some(note) = sounding
+                            .get_mut(&(channel, data_1))
+                            .and_then(|notes| notes.pop_front())
+                        {
+                            note_events.push(NoteEvent {
+                                id: note.id,
+                                start_ticks: note.start_ticks,
+                                end_ticks: ticks,
+                                note_number: data_1,
+                                velocity: note.velocity,
+                                channel,
+                                track_id: track_index,
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => return Err(SmfError::InvalidStatus(status)),
+        }
+    }
+
+    // Notes that are never released end with the track.
+    let mut unreleased: Vec<NoteEvent> = sounding
+        .into_iter()
+        .flat_map(|((channel, note_number), notes)| {
+            notes.into_iter().map(move |note| NoteEvent {
+                id: note.id,
+                start_ticks: note.start_ticks,
+                end_ticks: ticks,
+                note_number,
+                velocity: note.velocity,
+                channel,
+                track_id: track_index,
+            })
+        })
+        .collect();
+    unreleased.sort_by_key(|event| (event.start_ticks, event.note_number));
+    note_events.extend(unreleased);
+
+    Ok(())
+}
+
+pub fn import_smf(bytes: &[u8], store: &mut impl NoteEventStore) -> Result<SmfHeader, SmfError> {
+    let smf = Smf::parse(bytes)?;
+    store.add_events(smf.note_events)?;
+    Ok(smf.header)
+}
+
+/// Like `import_smf`, also keeping the controller, program change, pressure
+/// and pitch bend messages. Nothing is imported if either store rejects an
+/// event.
+pub fn import_smf_with_control_events(
+    bytes: &[u8],
+    store: &mut impl NoteEventStore,
+    control_store: &mut ControlEventStore,
+) -> Result<SmfHeader, SmfError> {
+    let smf = Smf::parse(bytes)?;
+
+    // Control events cannot have an invalid range, so a clashing id is the
+    // only way adding them can fail.
+    if let Some(event) = smf
+        .control_events
+        .iter()
+        .find(|event| control_store.get_event(&event.id).is_some())
+    {
+        return Err(NoteEventStoreError::DuplicateId(event.id.clone()).into());
+    }
+
+    store.add_events(smf.note_events)?;
+    for event in smf.control_events {
+        control_store
+            .add_event(event)
+            .expect("control event ids were checked before importing");
+    }
+    Ok(smf.header)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::note_event_store::{btree::BTreeNoteEventStore, control_event::PITCH_BEND_CENTER};
+
+    use super::*;
+
+    fn chunk(id: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend((data.len() as u32).to_be_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    fn header(format: u16, track_count: u16, division: u16) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend(format.to_be_bytes());
+        data.extend(track_count.to_be_bytes());
+        data.extend(division.to_be_bytes());
+        chunk(b"MThd", &data)
+    }
+
+    fn sorted_by_id(mut events: Vec<NoteEvent>) -> Vec<NoteEvent> {
+        events.sort_by(|a, b| a.id.cmp(&b.id));
+        events
+    }
+
+    #[test]
+    fn test_parse_single_track() {
+        let mut bytes = header(0, 1, 480);
+        bytes.extend(chunk(
+            b"MTrk",
+            &[
+                0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // tempo
+                0x00, 0x90, 0x3C, 0x64, // note on C4
+                0x00, 0x40, 0x50, // note on E4, running status
+                0x83, 0x60, 0x3C, 0x00, // note off C4 at 480, velocity 0
+                0x83, 0x60, 0x80, 0x40, 0x00, // note off E4 at 960
+                0x00, 0xFF, 0x2F, 0x00, // end of track
+            ],
+        ));
+
+        let smf = Smf::parse(&bytes).unwrap();
+
+        assert_eq!(
+            smf.header,
+            SmfHeader {
+                format: SmfFormat::SingleTrack,
+                track_count: 1,
+                ppq: 480,
+            }
+        );
+        assert_eq!(
+            sorted_by_id(smf.note_events),
+            vec![
+                NoteEvent {
+                    id: "0-0".to_string(),
+                    start_ticks: 0,
+                    end_ticks: 480,
+                    note_number: 60,
+                    velocity: 100,
+                    channel: 0,
+                    track_id: 0,
+                },
+                NoteEvent {
+                    id: "0-1".to_string(),
+                    start_ticks: 0,
+                    end_ticks: 960,
+                    note_number: 64,
+                    velocity: 80,
+                    channel: 0,
+                    track_id: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_multi_track() {
+        let mut bytes = header(1, 2, 96);
+        bytes.extend(chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]));
+        // Unknown chunks are skipped and do not count as tracks.
+        bytes.extend(chunk(b"XFIH", &[0x01, 0x02]));
+        bytes.extend(chunk(
+            b"MTrk",
+            &[
+                0x60, 0x91, 0x30, 0x40, // note on at 96
+                0x60, 0x81, 0x30, 0x00, // note off at 192
+                0x00, 0xFF, 0x2F, 0x00,
+            ],
+        ));
+
+        let smf = Smf::parse(&bytes).unwrap();
+
+        assert_eq!(smf.header.format, SmfFormat::MultiTrack);
+        assert_eq!(
+            smf.note_events,
+            vec![NoteEvent {
+                id: "1-0".to_string(),
+                start_ticks: 96,
+                end_ticks: 192,
+                note_number: 48,
+                velocity: 64,
+                channel: 1,
+                track_id: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_overlapping_same_pitch() {
+        let mut bytes = header(0, 1, 96);
+        bytes.extend(chunk(
+            b"MTrk",
+            &[
+                0x00, 0x90, 0x3C, 0x10, // first note on
+                0x0A, 0x3C, 0x20, // second note on at 10
+                0x0A, 0x3C, 0x00, // note off at 20 closes the first
+                0x0A, 0x3C, 0x00, // note off at 30 closes the second
+                0x00, 0xFF, 0x2F, 0x00,
+            ],
+        ));
+
+        let smf = Smf::parse(&bytes).unwrap();
+        let events = sorted_by_id(smf.note_events);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[0].start_ticks, events[0].end_ticks), (0, 20));
+        assert_eq!(events[0].velocity, 16);
+        assert_eq!((events[1].start_ticks, events[1].end_ticks), (10, 30));
+        assert_eq!(events[1].velocity, 32);
+    }
+
+    #[test]
+    fn test_parse_unreleased_note_ends_with_track() {
+        let mut bytes = header(0, 1, 96);
+        bytes.extend(chunk(
+            b"MTrk",
+            &[
+                0x00, 0x90, 0x3C, 0x64, // note on
+                0x00, 0xF0, 0x02, 0x7E, 0xF7, // sysex cancels running status
+                0x81, 0x00, 0xFF, 0x2F, 0x00, // end of track at 128
+            ],
+        ));
+
+        let smf = Smf::parse(&bytes).unwrap();
+
+        assert_eq!(smf.note_events.len(), 1);
+        assert_eq!(smf.note_events[0].end_ticks, 128);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Smf::parse(&chunk(b"MTrk", &[])).err(),
+            Some(SmfError::MissingHeader)
+        );
+        assert_eq!(
+            Smf::parse(&header(2, 1, 96)).err(),
+            Some(SmfError::UnsupportedFormat(2))
+        );
+        assert_eq!(
+            Smf::parse(&header(0, 1, 0xE250)).err(),
+            Some(SmfError::UnsupportedTimeDivision(0xE250))
+        );
+
+        let mut bytes = header(0, 1, 96);
+        bytes.extend(chunk(b"MTrk", &[0x00, 0x3C, 0x64]));
+        assert_eq!(Smf::parse(&bytes).err(), Some(SmfError::MissingStatus));
+
+        let mut bytes = header(0, 1, 96);
+        bytes.extend(chunk(b"MTrk", &[0x00, 0x90, 0x3C]));
+        assert_eq!(Smf::parse(&bytes).err(), Some(SmfError::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_import_smf() {
+        let mut bytes = header(0, 1, 480);
+        bytes.extend(chunk(
+            b"MTrk",
+            &[
+                0x00, 0x90, 0x3C, 0x64, // note on
+                0x83, 0x60, 0x80, 0x3C, 0x40, // note off at 480
+                0x00, 0xFF, 0x2F, 0x00,
+            ],
+        ));
+
+        let mut store = BTreeNoteEventStore::new();
+        let header = import_smf(&bytes, &mut store).unwrap();
+
+        assert_eq!(header.ppq, 480);
+        let event = store.get_event("0-0").unwrap();
+        assert_eq!(event.start_ticks, 0);
+        assert_eq!(event.end_ticks, 480);
+        assert_eq!(event.note_number, 60);
+    }
+
+    #[test]
+    fn test_parse_control_events() {
+        let mut bytes = header(0, 1, 96);
+        bytes.extend(chunk(
+            b"MTrk",
+            &[
+                0x00, 0xB0, 0x00, 0x01, // bank select
+                0x00, 0xC0, 0x05, // program change
+                0x00, 0x90, 0x3C, 0x64, // note on
+                0x0A, 0xE0, 0x00, 0x40, // pitch bend center
+                0x0A, 0x7F, 0x7F, // pitch bend max, running status
+                0x0A, 0xD0, 0x30, // channel pressure
+                0x00, 0xA0, 0x3C, 0x20, // poly aftertouch
+                0x0A, 0x80, 0x3C, 0x40, // note off
+                0x00, 0xFF, 0x2F, 0x00,
+            ],
+        ));
+
+        let smf = Smf::parse(&bytes).unwrap();
+
+        assert_eq!(smf.note_events.len(), 1);
+        assert_eq!(
+            smf.control_events
+                .iter()
+                .map(|e| (e.id.as_str(), e.ticks, e.message))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "0-c0",
+                    0,
+                    ControlMessage::ControlChange {
+                        controller: 0,
+                        value: 1,
+                    },
+                ),
+                ("0-c1", 0, ControlMessage::ProgramChange(5)),
+                ("0-c2", 10, ControlMessage::PitchBend(PITCH_BEND_CENTER)),
+                ("0-c3", 20, ControlMessage::PitchBend(16383)),
+                ("0-c4", 30, ControlMessage::ChannelPressure(0x30)),
+                (
+                    "0-c5",
+                    30,
+                    ControlMessage::PolyAftertouch {
+                        note_number: 60,
+                        pressure: 0x20,
+                    },
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_smf_with_control_events() {
+        let mut bytes = header(0, 1, 96);
+        bytes.extend(chunk(
+            b"MTrk",
+            &[
+                0x00, 0xB0, 0x07, 0x64, // volume
+                0x00, 0x90, 0x3C, 0x64, // note on
+                0x60, 0x80, 0x3C, 0x40, // note off
+                0x00, 0xFF, 0x2F, 0x00,
+            ],
+        ));
+
+        let mut store = BTreeNoteEventStore::new();
+        let mut control_store = ControlEventStore::new();
+        import_smf_with_control_events(&bytes, &mut store, &mut control_store).unwrap();
+
+        assert!(store.get_event("0-0").is_some());
+        assert_eq!(
+            control_store.get_event("0-c0").unwrap().message,
+            ControlMessage::ControlChange {
+                controller: 7,
+                value: 100,
+            }
+        );
+
+        // Importing again clashes with the control events and adds nothing.
+        let mut store = BTreeNoteEventStore::new();
+        assert_eq!(
+            import_smf_with_control_events(&bytes, &mut store, &mut control_store),
+            Err(SmfError::Store(NoteEventStoreError::DuplicateId(
+                "0-c0".to_string()
+            )))
+        );
+        assert_eq!(store.iter_events().count(), 0);
+    }
+}
@@ -0,0 +1,578 @@
+use std::collections::BTreeMap;
+
+use crate::note_event_store::{
+    base::NoteEventStore,
+    control_event::{ControlEvent, ControlEventStore, ControlMessage},
+    event::NoteEvent,
+};
+
+use super::{error::SmfError, header::SmfFormat};
+
+const MAX_VARIABLE_LENGTH: u64 = 0x0FFF_FFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmfExportOptions {
+    pub format: SmfFormat,
+    pub ppq: u16,
+}
+
+impl Default for SmfExportOptions {
+    fn default() -> Self {
+        SmfExportOptions {
+            format: SmfFormat::SingleTrack,
+            ppq: 480,
+        }
+    }
+}
+
+struct Message {
+    ticks: u64,
+    // Note-offs sort before note-ons on the same tick so back-to-back notes of
+    // the same pitch are not cut short, except for zero-length notes which
+    // must still be switched on before they are switched off. Control events
+    // go in between so they apply to the notes starting with them.
+    order: u8,
+    // Breaks ties within the same order: the note number for notes and the
+    // position in the sorted control events for those.
+    rank: usize,
+    status: u8,
+    data_1: u8,
+    data_2: Option<u8>,
+}
+
+fn control_message(event: &ControlEvent, rank: usize) -> Message {
+    let (status, data_1, data_2) = match event.message {
+        ControlMessage::ControlChange { controller, value } => {
+            (0xB0, controller & 0x7F, Some(value & 0x7F))
+        }
+        ControlMessage::ProgramChange(program) => (0xC0, program & 0x7F, None),
+        ControlMessage::ChannelPressure(pressure) => (0xD0, pressure & 0x7F, None),
+        ControlMessage::PolyAftertouch {
+            note_number,
+            pressure,
+        } => (0xA0, note_number & 0x7F, Some(pressure & 0x7F)),
+        ControlMessage::PitchBend(value) => {
+            let value = value.min(0x3FFF);
+            (0xE0, (value & 0x7F) as u8, Some((value >> 7) as u8))
+        }
+    };
+
+    Message {
+        ticks: event.ticks,
+        order: 1,
+        rank,
+        status: status | event.channel & 0x0F,
+        data_1,
+        data_2,
+    }
+}
+
+fn write_variable_length(bytes: &mut Vec<u8>, value: u64) -> Result<(), SmfError> {
+    if value > MAX_VARIABLE_LENGTH {
+        return Err(SmfError::DeltaTimeOverflow(value));
+    }
+
+    let mut groups = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        groups.push((rest & 0x7F) as u8 | 0x80);
+        rest >>= 7;
+    }
+    bytes.extend(groups.into_iter().rev());
+    Ok(())
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8], data: &[u8]) {
+    bytes.extend(id);
+    bytes.extend((data.len() as u32).to_be_bytes());
+    bytes.extend(data);
+}
+
+fn write_end_of_track(track: &mut Vec<u8>) {
+    track.extend([0x00, 0xFF, 0x2F, 0x00]);
+}
+
+fn write_track<'a>(
+    events: impl Iterator<Item = &'a NoteEvent>,
+    control_events: impl Iterator<Item = &'a ControlEvent>,
+) -> Result<Vec<u8>, SmfError> {
+    let mut messages: Vec<Message> = events
+        .flat_map(|event| {
+            let note_number = event.note_number.min(0x7F);
+            let channel = event.channel & 0x0F;
+            [
+                Message {
+                    ticks: event.start_ticks,
+                    order: 2,
+                    rank: note_number as usize,
+                    status: 0x90 | channel,
+                    data_1: note_number,
+                    // A note-on with velocity 0 would be read back as a note-off.
+                    data_2: Some(event.velocity.clamp(1, 0x7F)),
+                },
+                Message {
+                    ticks: event.end_ticks,
+                    order: if event.end_ticks == event.start_ticks {
+                        3
+                    } else {
+                        0
+                    },
+                    rank: note_number as usize,
+                    status: 0x80 | channel,
+                    data_1: note_number,
+                    data_2: Some(0x40),
+                },
+            ]
+        })
+        .collect();
+
+    let mut control_events: Vec<&ControlEvent> = control_events.collect();
+    control_events.sort_unstable_by(|a, b| a.compare(b));
+    messages.extend(
+        control_events
+            .into_iter()
+            .enumerate()
+            .map(|(rank, event)| control_message(event, rank)),
+    );
+
+    messages.sort_by_key(|message| (message.ticks, message.order, message.rank));
+
+    let mut track = vec![];
+    let mut ticks = 0;
+    let mut running_status = None;
+    for message in messages {
+        write_variable_length(&mut track, message.ticks - ticks)?;
+        ticks = message.ticks;
+
+        if running_status != Some(message.status) {
+            track.push(message.status);
+            running_status = Some(message.status);
+        }
+        track.push(message.data_1);
+        track.extend(message.data_2);
+    }
+    write_end_of_track(&mut track);
+
+    Ok(track)
+}
+
+pub fn export_smf(
+    store: &impl NoteEventStore,
+    options: &SmfExportOptions,
+) -> Result<Vec<u8>, SmfError> {
+    write_smf(store, None, options)
+}
+
+/// Like `export_smf`, also writing the control events.
+pub fn export_smf_with_control_events(
+    store: &impl NoteEventStore,
+    control_store: &ControlEventStore,
+    options: &SmfExportOptions,
+) -> Result<Vec<u8>, SmfError> {
+    write_smf(store, Some(control_store), options)
+}
+
+fn write_smf(
+    store: &impl NoteEventStore,
+    control_store: Option<&ControlEventStore>,
+    options: &SmfExportOptions,
+) -> Result<Vec<u8>, SmfError> {
+    if options.ppq == 0 || options.ppq & 0x8000 != 0 {
+        return Err(SmfError::UnsupportedTimeDivision(options.ppq));
+    }
+
+    let control_events = control_store
+        .into_iter()
+        .flat_map(|store| store.iter_events());
+
+    let tracks = match options.format {
+        SmfFormat::SingleTrack => vec![write_track(store.iter_events(), control_events)?],
+        SmfFormat::MultiTrack => {
+            let mut events_by_track: BTreeMap<u16, (Vec<&NoteEvent>, Vec<&ControlEvent>)> =
+                BTreeMap::new();
+            for event in store.iter_events() {
+                events_by_track
+                    .entry(event.track_id)
+                    .or_default()
+                    .0
+                    .push(event);
+            }
+            for event in control_events {
+                events_by_track
+                    .entry(event.track_id)
+                    .or_default()
+                    .1
+                    .push(event);
+            }
+
+            // Each track id is written to the chunk at that index, so track 0
+            // is the conductor track and is written even when it is empty.
+            let last_track_id = events_by_track.keys().next_back().copied().unwrap_or(0);
+            if last_track_id == u16::MAX {
+                return Err(SmfError::TooManyTracks);
+            }
+            (0..=last_track_id)
+                .map(|track_id| {
+                    let (events, control_events) =
+                        events_by_track.remove(&track_id).unwrap_or_default();
+                    write_track(events.into_iter(), control_events.into_iter())
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    let mut header = vec![];
+    header.extend(options.format.to_u16().to_be_bytes());
+    header.extend((tracks.len() as u16).to_be_bytes());
+    header.extend(options.ppq.to_be_bytes());
+
+    let mut bytes = vec![];
+    write_chunk(&mut bytes, b"MThd", &header);
+    for track in tracks {
+        write_chunk(&mut bytes, b"MTrk", &track);
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        note_event_store::{btree::BTreeNoteEventStore, vec::VecNoteEventStore},
+        smf::{
+            header::SmfHeader,
+            reader::{import_smf, import_smf_with_control_events, Smf},
+        },
+    };
+
+    use super::*;
+
+    fn note_fields(mut events: Vec<&NoteEvent>) -> Vec<(u64, u64, u8, u8, u8, u16)> {
+        events.sort_by_key(|e| (e.start_ticks, e.note_number));
+        events
+            .into_iter()
+            .map(|e| {
+                (
+                    e.start_ticks,
+                    e.end_ticks,
+                    e.note_number,
+                    e.velocity,
+                    e.channel,
+                    e.track_id,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_write_variable_length() {
+        for (value, expected) in [
+            (0x00, vec![0x00]),
+            (0x7F, vec![0x7F]),
+            (0x80, vec![0x81, 0x00]),
+            (0x2000, vec![0xC0, 0x00]),
+            (0x1F_FFFF, vec![0xFF, 0xFF, 0x7F]),
+            (0x0FFF_FFFF, vec![0xFF, 0xFF, 0xFF, 0x7F]),
+        ] {
+            let mut bytes = vec![];
+            write_variable_length(&mut bytes, value).unwrap();
+            assert_eq!(bytes, expected);
+        }
+
+        assert_eq!(
+            write_variable_length(&mut vec![], 0x1000_0000),
+            Err(SmfError::DeltaTimeOverflow(0x1000_0000))
+        );
+    }
+
+    #[test]
+    fn test_export_single_track() {
+        let mut store = VecNoteEventStore::new();
+        store
+            .add_events(vec![
+                NoteEvent {
+                    id: "0".to_string(),
+                    start_ticks: 0,
+                    end_ticks: 96,
+                    note_number: 60,
+                    velocity: 100,
+                    channel: 1,
+                    track_id: 0,
+                },
+                NoteEvent {
+                    id: "1".to_string(),
+                    start_ticks: 96,
+                    end_ticks: 192,
+                    note_number: 60,
+                    velocity: 0,
+                    channel: 1,
+                    track_id: 0,
+                },
+            ])
+            .unwrap();
+
+        let bytes = export_smf(
+            &store,
+            &SmfExportOptions {
+                format: SmfFormat::SingleTrack,
+                ppq: 96,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            bytes,
+            vec![
+                b'M', b'T', b'h', b'd', 0x00, 0x00, 0x00, 0x06, // header chunk
+                0x00, 0x00, 0x00, 0x01, 0x00, 0x60, // format 0, 1 track, 96 ppq
+                b'M', b'T', b'r', b'k', 0x00, 0x00, 0x00, 0x14, // track chunk
+                0x00, 0x91, 0x3C, 0x64, // note on
+                0x60, 0x81, 0x3C, 0x40, // note off before the next note on
+                0x00, 0x91, 0x3C, 0x01, // velocity 0 is raised to 1
+                0x60, 0x81, 0x3C, 0x40, // note off
+                0x00, 0xFF, 0x2F, 0x00, // end of track
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_zero_length_note() {
+        let mut store = VecNoteEventStore::new();
+        store
+            .add_event(NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 10,
+                end_ticks: 10,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            })
+            .unwrap();
+
+        let bytes = export_smf(&store, &SmfExportOptions::default()).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+
+        assert_eq!(smf.note_events.len(), 1);
+        assert_eq!(smf.note_events[0].start_ticks, 10);
+        assert_eq!(smf.note_events[0].end_ticks, 10);
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let mut store = BTreeNoteEventStore::new();
+        store
+            .add_events(
+                (0..500)
+                    .map(|i| NoteEvent {
+                        id: i.to_string(),
+                        start_ticks: i * 10,
+                        end_ticks: i * 10 + 5 + i % 300,
+                        note_number: 36 + (i % 48) as u8,
+                        velocity: 1 + (i % 127) as u8,
+                        channel: (i % 16) as u8,
+                        track_id: 1 + (i % 2) as u16,
+                    })
+                    .collect(),
+            )
+            .unwrap();
+
+        for format in [SmfFormat::SingleTrack, SmfFormat::MultiTrack] {
+            let bytes = export_smf(&store, &SmfExportOptions { format, ppq: 960 }).unwrap();
+
+            let mut imported = VecNoteEventStore::new();
+            let header = import_smf(&bytes, &mut imported).unwrap();
+
+            assert_eq!(
+                header,
+                SmfHeader {
+                    format,
+                    // Format 1 keeps the empty conductor track 0.
+                    track_count: if format == SmfFormat::SingleTrack {
+                        1
+                    } else {
+                        3
+                    },
+                    ppq: 960,
+                }
+            );
+
+            let mut expected = note_fields(store.iter_events().collect());
+            if format == SmfFormat::SingleTrack {
+                // Format 0 files only have one track to put everything on.
+                for fields in expected.iter_mut() {
+                    fields.5 = 0;
+                }
+            }
+            assert_eq!(note_fields(imported.iter_events().collect()), expected);
+        }
+    }
+
+    #[test]
+    fn test_export_invalid_ppq() {
+        let store = VecNoteEventStore::new();
+
+        for ppq in [0, 0x8000] {
+            assert_eq!(
+                export_smf(
+                    &store,
+                    &SmfExportOptions {
+                        ppq,
+                        ..SmfExportOptions::default()
+                    },
+                ),
+                Err(SmfError::UnsupportedTimeDivision(ppq))
+            );
+        }
+    }
+
+    #[test]
+    fn test_export_control_events() {
+        let mut store = VecNoteEventStore::new();
+        store
+            .add_event(NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 96,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: 0,
+            })
+            .unwrap();
+        let mut control_store = ControlEventStore::new();
+        for (id, ticks, message) in [
+            ("0", 0, ControlMessage::ProgramChange(5)),
+            (
+                "1",
+                0,
+                ControlMessage::ControlChange {
+                    controller: 0,
+                    value: 1,
+                },
+            ),
+            ("2", 48, ControlMessage::PitchBend(0x3FFF)),
+            ("3", 96, ControlMessage::ChannelPressure(0x30)),
+        ] {
+            control_store
+                .add_event(ControlEvent {
+                    id: id.to_string(),
+                    ticks,
+                    message,
+                    channel: 0,
+                    track_id: 0,
+                })
+                .unwrap();
+        }
+
+        let bytes = export_smf_with_control_events(
+            &store,
+            &control_store,
+            &SmfExportOptions {
+                format: SmfFormat::SingleTrack,
+                ppq: 96,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            bytes[22..],
+            [
+                0x00, 0xB0, 0x00, 0x01, // bank select before the program change
+                0x00, 0xC0, 0x05, // program change before the note on
+                0x00, 0x90, 0x3C, 0x64, // note on
+                0x30, 0xE0, 0x7F, 0x7F, // pitch bend
+                0x30, 0x80, 0x3C, 0x40, // note off
+                0x00, 0xD0, 0x30, // channel pressure
+                0x00, 0xFF, 0x2F, 0x00,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_control_events_round_trip() {
+        let mut store = BTreeNoteEventStore::new();
+        let mut control_store = ControlEventStore::new();
+        for i in 0..100 {
+            store
+                .add_event(NoteEvent {
+                    id: i.to_string(),
+                    start_ticks: i * 10,
+                    end_ticks: i * 10 + 5,
+                    note_number: 60,
+                    velocity: 100,
+                    channel: 0,
+                    track_id: 0,
+                })
+                .unwrap();
+            control_store
+                .add_event(ControlEvent {
+                    id: i.to_string(),
+                    ticks: i * 10 + 2,
+                    message: match i % 5 {
+                        0 => ControlMessage::ControlChange {
+                            controller: 1,
+                            value: i as u8,
+                        },
+                        1 => ControlMessage::ProgramChange(i as u8),
+                        2 => ControlMessage::ChannelPressure(i as u8),
+                        3 => ControlMessage::PolyAftertouch {
+                            note_number: 60,
+                            pressure: i as u8,
+                        },
+                        _ => ControlMessage::PitchBend(i as u16 * 100),
+                    },
+                    channel: 0,
+                    track_id: 0,
+                })
+                .unwrap();
+        }
+
+        let bytes =
+            export_smf_with_control_events(&store, &control_store, &SmfExportOptions::default())
+                .unwrap();
+
+        let mut imported = VecNoteEventStore::new();
+        let mut imported_control_store = ControlEventStore::new();
+        import_smf_with_control_events(&bytes, &mut imported, &mut imported_control_store).unwrap();
+
+        let messages = |store: &ControlEventStore| -> Vec<(u64, ControlMessage)> {
+            store
+                .get_events_by_range(0, u64::MAX)
+                .into_iter()
+                .map(|e| (e.ticks, e.message))
+                .collect()
+        };
+
+        assert_eq!(imported.iter_events().count(), 100);
+        assert_eq!(messages(&imported_control_store), messages(&control_store));
+    }
+
+    #[test]
+    fn test_export_too_many_tracks() {
+        let mut store = VecNoteEventStore::new();
+        store
+            .add_event(NoteEvent {
+                id: "0".to_string(),
+                start_ticks: 0,
+                end_ticks: 10,
+                note_number: 60,
+                velocity: 100,
+                channel: 0,
+                track_id: u16::MAX,
+            })
+            .unwrap();
+
+        assert_eq!(
+            export_smf(
+                &store,
+                &SmfExportOptions {
+                    format: SmfFormat::MultiTrack,
+                    ..SmfExportOptions::default()
+                },
+            ),
+            Err(SmfError::TooManyTracks)
+        );
+        assert!(export_smf(&store, &SmfExportOptions::default()).is_ok());
+    }
+}
@@ -0,0 +1,341 @@
+use std::{error::Error, fmt, ops::RangeInclusive};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeterMapError {
+    InvalidPpq(u16),
+    InvalidTimeSignature(TimeSignature),
+    InvalidBar(u32),
+}
+
+impl fmt::Display for MeterMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeterMapError::InvalidPpq(ppq) => write!(f, "invalid ppq {}", ppq),
+            MeterMapError::InvalidTimeSignature(time_signature) => write!(
+                f,
+                "invalid time signature {}/{}",
+                time_signature.numerator, time_signature.denominator
+            ),
+            MeterMapError::InvalidBar(bar) => write!(f, "invalid bar {}", bar),
+        }
+    }
+}
+
+impl Error for MeterMapError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSignature {
+    pub numerator: u8,
+    pub denominator: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeterChange {
+    /// The 1-based bar the time signature starts at.
+    pub bar: u32,
+    pub time_signature: TimeSignature,
+}
+
+/// A position with a 1-based bar and beat, and a 0-based tick within the beat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BarBeatTick {
+    pub bar: u32,
+    pub beat: u32,
+    pub tick: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MeterMap {
+    ppq: u16,
+    // Sorted by bar, always starting with a change at bar 1.
+    changes: Vec<MeterChange>,
+    // Tick at which each change takes effect.
+    change_ticks: Vec<u64>,
+}
+
+impl MeterMap {
+    pub fn new(ppq: u16, time_signature: TimeSignature) -> Result<Self, MeterMapError> {
+        if ppq == 0 {
+            return Err(MeterMapError::InvalidPpq(ppq));
+        }
+        check_time_signature(ppq, time_signature)?;
+
+        Ok(MeterMap {
+            ppq,
+            changes: vec![MeterChange {
+                bar: 1,
+                time_signature,
+            }],
+            change_ticks: vec![0],
+        })
+    }
+
+    pub fn ppq(&self) -> u16 {
+        self.ppq
+    }
+
+    pub fn changes(&self) -> &[MeterChange] {
+        &self.changes
+    }
+
+    /// Adds a time signature change, replacing any existing change at the
+    /// same bar.
+    pub fn insert_change(&mut self, change: MeterChange) -> Result<(), MeterMapError> {
+        if change.bar == 0 {
+            return Err(MeterMapError::InvalidBar(change.bar));
+        }
+        check_time_signature(self.ppq, change.time_signature)?;
+
+        match self.changes.binary_search_by_key(&change.bar, |c| c.bar) {
+            Ok(index) => self.changes[index] = change,
+            Err(index) => self.changes.insert(index, change),
+        }
+        self.update_change_ticks();
+        Ok(())
+    }
+
+    /// Removes the time signature change at `bar`. The initial time signature
+    /// at bar 1 can be replaced but not removed.
+    pub fn remove_change(&mut self, bar: u32) -> Option<MeterChange> {
+        if bar <= 1 {
+            return None;
+        }
+        let index = self.changes.binary_search_by_key(&bar, |c| c.bar).ok()?;
+        let change = self.changes.remove(index);
+        self.update_change_ticks();
+        Some(change)
+    }
+
+    pub fn time_signature_at_bar(&self, bar: u32) -> TimeSignature {
+        self.changes[self.change_index_at_bar(bar)].time_signature
+    }
+
+    pub fn time_signature_at_ticks(&self, ticks: u64) -> TimeSignature {
+        self.changes[self.change_index_at_ticks(ticks)].time_signature
+    }
+
+    pub fn ticks_per_beat(&self, time_signature: TimeSignature) -> u64 {
+        self.ppq as u64 * 4 / time_signature.denominator as u64
+    }
+
+    pub fn ticks_per_bar(&self, time_signature: TimeSignature) -> u64 {
+        self.ticks_per_beat(time_signature) * time_signature.numerator as u64
+    }
+
+    /// The first tick of `bar`. Bar 0 is treated as bar 1.
+    pub fn bar_to_ticks(&self, bar: u32) -> u64 {
+        let bar = bar.max(1);
+        let index = self.change_index_at_bar(bar);
+        let change = &self.changes[index];
+        self.change_ticks[index]
+            + (bar - change.bar) as u64 * self.ticks_per_bar(change.time_signature)
+    }
+
+    pub fn ticks_to_bar_beat_tick(&self, ticks: u64) -> BarBeatTick {
+        let index = self.change_index_at_ticks(ticks);
+        let change = &self.changes[index];
+        let ticks_per_beat = self.ticks_per_beat(change.time_signature);
+        let ticks_per_bar = self.ticks_per_bar(change.time_signature);
+
+        let ticks_since_change = ticks - self.change_ticks[index];
+        let ticks_in_bar = ticks_since_change % ticks_per_bar;
+
+        BarBeatTick {
+            bar: change.bar + (ticks_since_change / ticks_per_bar) as u32,
+            beat: (ticks_in_bar / ticks_per_beat) as u32 + 1,
+            tick: ticks_in_bar % ticks_per_beat,
+        }
+    }
+
+    /// Beats and ticks past the end of the bar carry over into the following
+    /// bars' time.
+    pub fn bar_beat_tick_to_ticks(&self, position: BarBeatTick) -> u64 {
+        let time_signature = self.time_signature_at_bar(position.bar.max(1));
+        self.bar_to_ticks(position.bar)
+            + position.beat.saturating_sub(1) as u64 * self.ticks_per_beat(time_signature)
+            + position.tick
+    }
+
+    /// The first and last tick covered by `bars`, or `None` if the range is
+    /// empty. Bar 0 is treated as bar 1.
+    pub fn bars_to_ticks(&self, bars: RangeInclusive<u32>) -> Option<(u64, u64)> {
+        if bars.is_empty() {
+            return None;
+        }
+        let (start_bar, end_bar) = ((*bars.start()).max(1), (*bars.end()).max(1));
+        let start_ticks = self.bar_to_ticks(start_bar);
+        // Measured from the last bar itself, as the bar after it may not exist.
+        let end_ticks = self.bar_to_ticks(end_bar)
+            + self.ticks_per_bar(self.time_signature_at_bar(end_bar))
+            - 1;
+        Some((start_ticks, end_ticks))
+    }
+
+    fn change_index_at_bar(&self, bar: u32) -> usize {
+        self.changes.partition_point(|c| c.bar <= bar).max(1) - 1
+    }
+
+    fn change_index_at_ticks(&self, ticks: u64) -> usize {
+        self.change_ticks.partition_point(|t| *t <= ticks) - 1
+    }
+
+    fn update_change_ticks(&mut self) {
+        self.change_ticks = vec![0];
+        for index in 1..self.changes.len() {
+            let previous = &self.changes[index - 1];
+            let ticks = self.change_ticks[index - 1]
+                + (self.changes[index].bar - previous.bar) as u64
+                    * self.ticks_per_bar(previous.time_signature);
+            self.change_ticks.push(ticks);
+        }
+    }
+}
+
+fn check_time_signature(ppq: u16, time_signature: TimeSignature) -> Result<(), MeterMapError> {
+    let TimeSignature {
+        numerator,
+        denominator,
+    } = time_signature;
+    // The beat length must be a whole number of ticks.
+    if numerator == 0
+        || !denominator.is_power_of_two()
+        || !(ppq as u64 * 4).is_multiple_of(denominator as u64)
+    {
+        return Err(MeterMapError::InvalidTimeSignature(time_signature));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time_signature(numerator: u8, denominator: u8) -> TimeSignature {
+        TimeSignature {
+            numerator,
+            denominator,
+        }
+    }
+
+    fn bar_beat_tick(bar: u32, beat: u32, tick: u64) -> BarBeatTick {
+        BarBeatTick { bar, beat, tick }
+    }
+
+    #[test]
+    fn test_new() {
+        assert_eq!(
+            MeterMap::new(0, time_signature(4, 4)).err(),
+            Some(MeterMapError::InvalidPpq(0))
+        );
+        for invalid in [
+            time_signature(0, 4),
+            time_signature(4, 3),
+            time_signature(4, 0),
+        ] {
+            assert_eq!(
+                MeterMap::new(480, invalid).err(),
+                Some(MeterMapError::InvalidTimeSignature(invalid))
+            );
+        }
+        // 3 ticks per quarter note cannot be split into sixteenths.
+        assert!(MeterMap::new(3, time_signature(4, 16)).is_err());
+
+        let meter_map = MeterMap::new(480, time_signature(4, 4)).unwrap();
+
+        assert_eq!(meter_map.ppq(), 480);
+        assert_eq!(meter_map.changes().len(), 1);
+    }
+
+    #[test]
+    fn test_constant_meter() {
+        let meter_map = MeterMap::new(480, time_signature(4, 4)).unwrap();
+
+        assert_eq!(meter_map.bar_to_ticks(0), 0);
+        assert_eq!(meter_map.bar_to_ticks(1), 0);
+        assert_eq!(meter_map.bar_to_ticks(3), 3840);
+        assert_eq!(meter_map.ticks_to_bar_beat_tick(0), bar_beat_tick(1, 1, 0));
+        assert_eq!(
+            meter_map.ticks_to_bar_beat_tick(2400),
+            bar_beat_tick(2, 2, 0)
+        );
+        assert_eq!(
+            meter_map.ticks_to_bar_beat_tick(2401),
+            bar_beat_tick(2, 2, 1)
+        );
+        assert_eq!(
+            meter_map.bar_beat_tick_to_ticks(bar_beat_tick(2, 2, 1)),
+            2401
+        );
+        assert_eq!(meter_map.bars_to_ticks(3..=8), Some((3840, 15359)));
+        assert_eq!(meter_map.bars_to_ticks(1..=1), Some((0, 1919)));
+        assert_eq!(meter_map.bars_to_ticks(0..=0), Some((0, 1919)));
+        assert_eq!(meter_map.bars_to_ticks(0..=2), Some((0, 3839)));
+        let last_bar_ticks = meter_map.bar_to_ticks(u32::MAX);
+        assert_eq!(
+            meter_map.bars_to_ticks(u32::MAX..=u32::MAX),
+            Some((last_bar_ticks, last_bar_ticks + 1919))
+        );
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = 8..=3;
+        assert_eq!(meter_map.bars_to_ticks(empty), None);
+    }
+
+    #[test]
+    fn test_meter_changes() {
+        let mut meter_map = MeterMap::new(480, time_signature(4, 4)).unwrap();
+        meter_map
+            .insert_change(MeterChange {
+                bar: 3,
+                time_signature: time_signature(6, 8),
+            })
+            .unwrap();
+        meter_map
+            .insert_change(MeterChange {
+                bar: 5,
+                time_signature: time_signature(3, 4),
+            })
+            .unwrap();
+
+        // Bars 1-2 are 1920 ticks, bars 3-4 are 1440 ticks, then 1440 again.
+        assert_eq!(meter_map.bar_to_ticks(3), 3840);
+        assert_eq!(meter_map.bar_to_ticks(5), 6720);
+        assert_eq!(meter_map.bar_to_ticks(6), 8160);
+        assert_eq!(meter_map.time_signature_at_bar(4), time_signature(6, 8));
+        assert_eq!(
+            meter_map.time_signature_at_ticks(6719),
+            time_signature(6, 8)
+        );
+        assert_eq!(
+            meter_map.time_signature_at_ticks(6720),
+            time_signature(3, 4)
+        );
+
+        assert_eq!(
+            meter_map.ticks_to_bar_beat_tick(3840 + 240 * 5),
+            bar_beat_tick(3, 6, 0)
+        );
+        assert_eq!(
+            meter_map.ticks_to_bar_beat_tick(6720 + 1500),
+            bar_beat_tick(6, 1, 60)
+        );
+        for ticks in [0, 1919, 3840, 5279, 5280, 6720, 10000] {
+            let position = meter_map.ticks_to_bar_beat_tick(ticks);
+            assert_eq!(meter_map.bar_beat_tick_to_ticks(position), ticks);
+        }
+
+        assert_eq!(meter_map.bars_to_ticks(2..=3), Some((1920, 5279)));
+
+        assert_eq!(
+            meter_map.insert_change(MeterChange {
+                bar: 0,
+                time_signature: time_signature(4, 4),
+            }),
+            Err(MeterMapError::InvalidBar(0))
+        );
+
+        assert_eq!(meter_map.remove_change(3).map(|c| c.bar), Some(3));
+        assert_eq!(meter_map.remove_change(3), None);
+        assert_eq!(meter_map.remove_change(1), None);
+        assert_eq!(meter_map.bar_to_ticks(5), 7680);
+    }
+}
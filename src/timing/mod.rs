@@ -0,0 +1,2 @@
+pub mod meter_map;
+pub mod tempo_map;
@@ -0,0 +1,322 @@
+use std::{error::Error, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TempoMapError {
+    InvalidPpq(u16),
+    InvalidBpm(f64),
+}
+
+impl fmt::Display for TempoMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TempoMapError::InvalidPpq(ppq) => write!(f, "invalid ppq {}", ppq),
+            TempoMapError::InvalidBpm(bpm) => write!(f, "invalid bpm {}", bpm),
+        }
+    }
+}
+
+impl Error for TempoMapError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TempoCurve {
+    /// Holds the tempo until the next change.
+    #[default]
+    Constant,
+    /// Ramps the tempo linearly (per tick) up to the next change's tempo.
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoChange {
+    pub ticks: u64,
+    pub bpm: f64,
+    pub curve: TempoCurve,
+}
+
+#[derive(Debug, Clone)]
+pub struct TempoMap {
+    ppq: u16,
+    // Sorted by ticks, always starting with a change at tick 0.
+    changes: Vec<TempoChange>,
+    // Time in seconds at which each change takes effect.
+    change_seconds: Vec<f64>,
+}
+
+impl TempoMap {
+    pub fn new(ppq: u16, bpm: f64) -> Result<Self, TempoMapError> {
+        if ppq == 0 {
+            return Err(TempoMapError::InvalidPpq(ppq));
+        }
+        check_bpm(bpm)?;
+
+        Ok(TempoMap {
+            ppq,
+            changes: vec![TempoChange {
+                ticks: 0,
+                bpm,
+                curve: TempoCurve::Constant,
+            }],
+            change_seconds: vec![0.0],
+        })
+    }
+
+    pub fn ppq(&self) -> u16 {
+        self.ppq
+    }
+
+    pub fn changes(&self) -> &[TempoChange] {
+        &self.changes
+    }
+
+    /// Adds a tempo change, replacing any existing change at the same tick.
+    pub fn insert_change(&mut self, change: TempoChange) -> Result<(), TempoMapError> {
+        check_bpm(change.bpm)?;
+
+        match self
+            .changes
+            .binary_search_by_key(&change.ticks, |c| c.ticks)
+        {
+            Ok(index) => self.changes[index] = change,
+            Err(index) => self.changes.insert(index, change),
+        }
+        self.update_change_seconds();
+        Ok(())
+    }
+
+    /// Removes the tempo change at `ticks`. The initial tempo at tick 0 can be
+    /// replaced but not removed.
+    pub fn remove_change(&mut self, ticks: u64) -> Option<TempoChange> {
+        if ticks == 0 {
+            return None;
+        }
+        let index = self
+            .changes
+            .binary_search_by_key(&ticks, |c| c.ticks)
+            .ok()?;
+        let change = self.changes.remove(index);
+        self.update_change_seconds();
+        Some(change)
+    }
+
+    pub fn bpm_at(&self, ticks: u64) -> f64 {
+        let index = self.change_index_at_ticks(ticks as f64);
+        self.changes[index].bpm + self.slope(index) * (ticks - self.changes[index].ticks) as f64
+    }
+
+    pub fn ticks_to_seconds(&self, ticks: u64) -> f64 {
+        let index = self.change_index_at_ticks(ticks as f64);
+        self.change_seconds[index]
+            + self.segment_seconds(index, (ticks - self.changes[index].ticks) as f64)
+    }
+
+    /// Converts a time to fractional ticks. Negative times map to tick 0.
+    pub fn seconds_to_ticks(&self, seconds: f64) -> f64 {
+        let seconds = seconds.max(0.0);
+        let index = self
+            .change_seconds
+            .partition_point(|change_seconds| *change_seconds <= seconds)
+            - 1;
+        self.changes[index].ticks as f64
+            + self.segment_ticks(index, seconds - self.change_seconds[index])
+    }
+
+    /// The ticks bounding the events that sound between `start_seconds` and
+    /// `end_seconds`: an event overlaps the window if it ends at or after the
+    /// first tick and starts at or before the second. A window that falls
+    /// between two ticks yields a first tick greater than the second.
+    pub fn seconds_range_to_ticks(&self, start_seconds: f64, end_seconds: f64) -> (u64, u64) {
+        // Absorbs rounding errors so a window edge landing exactly on a tick
+        // keeps that tick.
+        const TICKS_EPSILON: f64 = 1e-6;
+
+        let start_ticks = (self.seconds_to_ticks(start_seconds) - TICKS_EPSILON).ceil();
+        let end_ticks = (self.seconds_to_ticks(end_seconds) + TICKS_EPSILON).floor();
+        (start_ticks.max(0.0) as u64, end_ticks.max(0.0) as u64)
+    }
+
+    fn change_index_at_ticks(&self, ticks: f64) -> usize {
+        self.changes.partition_point(|c| c.ticks as f64 <= ticks) - 1
+    }
+
+    // Change of bpm per tick over the segment starting at `index`.
+    fn slope(&self, index: usize) -> f64 {
+        let change = &self.changes[index];
+        match (change.curve, self.changes.get(index + 1)) {
+            (TempoCurve::Linear, Some(next)) => {
+                (next.bpm - change.bpm) / (next.ticks - change.ticks) as f64
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn segment_seconds(&self, index: usize, delta_ticks: f64) -> f64 {
+        let bpm = self.changes[index].bpm;
+        let slope = self.slope(index);
+        let ppq = self.ppq as f64;
+
+        if slope == 0.0 {
+            delta_ticks * 60.0 / (bpm * ppq)
+        } else {
+            60.0 / (ppq * slope) * ((bpm + slope * delta_ticks) / bpm).ln()
+        }
+    }
+
+    fn segment_ticks(&self, index: usize, delta_seconds: f64) -> f64 {
+        let bpm = self.changes[index].bpm;
+        let slope = self.slope(index);
+        let ppq = self.ppq as f64;
+
+        if slope == 0.0 {
+            delta_seconds * bpm * ppq / 60.0
+        } else {
+            bpm * ((delta_seconds * ppq * slope / 60.0).exp() - 1.0) / slope
+        }
+    }
+
+    fn update_change_seconds(&mut self) {
+        self.change_seconds = vec![0.0];
+        for index in 1..self.changes.len() {
+            let delta_ticks = (self.changes[index].ticks - self.changes[index - 1].ticks) as f64;
+            let seconds =
+                self.change_seconds[index - 1] + self.segment_seconds(index - 1, delta_ticks);
+            self.change_seconds.push(seconds);
+        }
+    }
+}
+
+fn check_bpm(bpm: f64) -> Result<(), TempoMapError> {
+    if !bpm.is_finite() || bpm <= 0.0 {
+        return Err(TempoMapError::InvalidBpm(bpm));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_new() {
+        assert_eq!(
+            TempoMap::new(0, 120.0).err(),
+            Some(TempoMapError::InvalidPpq(0))
+        );
+        assert_eq!(
+            TempoMap::new(480, 0.0).err(),
+            Some(TempoMapError::InvalidBpm(0.0))
+        );
+        assert!(TempoMap::new(480, f64::NAN).is_err());
+
+        let tempo_map = TempoMap::new(480, 120.0).unwrap();
+
+        assert_eq!(tempo_map.ppq(), 480);
+        assert_eq!(tempo_map.changes().len(), 1);
+    }
+
+    #[test]
+    fn test_constant_tempo() {
+        let tempo_map = TempoMap::new(480, 120.0).unwrap();
+
+        assert_close(tempo_map.ticks_to_seconds(0), 0.0);
+        assert_close(tempo_map.ticks_to_seconds(480), 0.5);
+        assert_close(tempo_map.ticks_to_seconds(960 * 4), 4.0);
+        assert_close(tempo_map.seconds_to_ticks(0.5), 480.0);
+        assert_close(tempo_map.seconds_to_ticks(-1.0), 0.0);
+        assert_close(tempo_map.bpm_at(100_000), 120.0);
+    }
+
+    #[test]
+    fn test_tempo_changes() {
+        let mut tempo_map = TempoMap::new(480, 120.0).unwrap();
+        tempo_map
+            .insert_change(TempoChange {
+                ticks: 960,
+                bpm: 60.0,
+                curve: TempoCurve::Constant,
+            })
+            .unwrap();
+
+        assert_close(tempo_map.ticks_to_seconds(960), 1.0);
+        assert_close(tempo_map.ticks_to_seconds(1440), 2.0);
+        assert_close(tempo_map.seconds_to_ticks(2.0), 1440.0);
+        assert_close(tempo_map.seconds_to_ticks(0.75), 720.0);
+
+        // Replacing the change at the same tick.
+        tempo_map
+            .insert_change(TempoChange {
+                ticks: 960,
+                bpm: 240.0,
+                curve: TempoCurve::Constant,
+            })
+            .unwrap();
+
+        assert_eq!(tempo_map.changes().len(), 2);
+        assert_close(tempo_map.ticks_to_seconds(1440), 1.25);
+
+        assert_eq!(tempo_map.remove_change(960).map(|c| c.bpm), Some(240.0));
+        assert_eq!(tempo_map.remove_change(960), None);
+        assert_eq!(tempo_map.remove_change(0), None);
+        assert_close(tempo_map.ticks_to_seconds(1440), 1.5);
+
+        assert_eq!(
+            tempo_map.insert_change(TempoChange {
+                ticks: 10,
+                bpm: -1.0,
+                curve: TempoCurve::Constant,
+            }),
+            Err(TempoMapError::InvalidBpm(-1.0))
+        );
+    }
+
+    #[test]
+    fn test_seconds_range_to_ticks() {
+        let tempo_map = TempoMap::new(480, 120.0).unwrap();
+
+        assert_eq!(tempo_map.seconds_range_to_ticks(0.5, 1.0), (480, 960));
+        assert_eq!(tempo_map.seconds_range_to_ticks(0.1, 0.2), (96, 192));
+        // Between ticks 96 and 97.
+        assert_eq!(tempo_map.seconds_range_to_ticks(0.1001, 0.1002), (97, 96));
+    }
+
+    #[test]
+    fn test_linear_ramp() {
+        let mut tempo_map = TempoMap::new(100, 60.0).unwrap();
+        tempo_map
+            .insert_change(TempoChange {
+                ticks: 0,
+                bpm: 60.0,
+                curve: TempoCurve::Linear,
+            })
+            .unwrap();
+        tempo_map
+            .insert_change(TempoChange {
+                ticks: 400,
+                bpm: 120.0,
+                curve: TempoCurve::Constant,
+            })
+            .unwrap();
+
+        assert_close(tempo_map.bpm_at(200), 90.0);
+        assert_close(tempo_map.bpm_at(400), 120.0);
+
+        // 60 / (ppq * slope) * ln(bpm_end / bpm_start)
+        let ramp_seconds = 60.0 / (100.0 * 0.15) * 2f64.ln();
+
+        assert_close(tempo_map.ticks_to_seconds(400), ramp_seconds);
+        assert_close(tempo_map.ticks_to_seconds(500), ramp_seconds + 0.5);
+
+        for ticks in [0, 1, 150, 399, 400, 1000] {
+            let seconds = tempo_map.ticks_to_seconds(ticks);
+            assert_close(tempo_map.seconds_to_ticks(seconds), ticks as f64);
+        }
+    }
+}